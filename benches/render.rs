@@ -0,0 +1,105 @@
+//! Renders of the default view, comparing the formula behind a `dyn PlottingAlgorithm`, as the
+//! renderer drew before `Canvas` was generic, with the loops compiled for it, and the pixels
+//! worked out one by one with `pixel_to_point` against stepping across the rows.
+//!
+//! Compiling the loops for the formula only pays off for formulas without a batch kernel whose
+//! steps are cheap next to a call, like the Burning Ship. The escape time algorithm hands a row
+//! at a time to its lane kernel either way, and the Phoenix formula's steps outweigh the call.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mandelbrot::algorithms::{
+    default_escape_radius, Algorithm, Bailout, Escape, Parameters, PlottingAlgorithm, WithAlgorithm,
+};
+use mandelbrot::coloring::Coloring;
+use mandelbrot::mandelbrot::{calculate_corners, pixel_to_point, row_points, Canvas};
+use num::Complex;
+use std::hint::black_box;
+
+const BOUNDS: (usize, usize) = (240, 180);
+const LIMIT: usize = 256;
+
+/// The formula called `name`, at the escape radius and parameters the command line defaults to
+fn formula(name: &str) -> Algorithm {
+    let escape = Escape::new(default_escape_radius(name), Bailout::Circle);
+    Algorithm::new(name, escape, &Parameters::default()).unwrap()
+}
+
+/// The corners of the default view of the formula
+fn corners(algorithm: &Algorithm) -> (Complex<f64>, Complex<f64>) {
+    let (zoom, center) = algorithm.as_dyn().default_view();
+    calculate_corners(zoom, center)
+}
+
+/// A render of the whole image through a canvas of whatever formula it is given
+struct Render<'a, 'b> {
+    criterion: &'a mut Criterion,
+    name: &'b str,
+    corners: (Complex<f64>, Complex<f64>),
+}
+
+impl WithAlgorithm for Render<'_, '_> {
+    type Output = ();
+
+    fn run<A: PlottingAlgorithm + Send + Sync + ?Sized>(self, algorithm: &A) {
+        let canvas = Canvas::new(algorithm, Coloring::Iterations);
+        render(self.criterion, &format!("static/{}", self.name), &canvas, self.corners);
+    }
+}
+
+fn render<P: PlottingAlgorithm>(
+    criterion: &mut Criterion, id: &str, canvas: &Canvas<P>,
+    (upper_left, lower_right): (Complex<f64>, Complex<f64>),
+) {
+    let mut pixels = vec![0; BOUNDS.0 * BOUNDS.1];
+    criterion.bench_function(id, |bencher| {
+        bencher.iter(|| {
+            canvas.render(&mut pixels, BOUNDS, upper_left, lower_right, LIMIT, false);
+            black_box(&pixels);
+        })
+    });
+}
+
+fn dispatch(criterion: &mut Criterion) {
+    for name in ["escape_time", "burning_ship", "phoenix"] {
+        let algorithm = formula(name);
+        let corners = corners(&algorithm);
+        let boxed = formula(name).into_boxed();
+        let canvas = Canvas::new(boxed, Coloring::Iterations);
+        render(criterion, &format!("dyn/{}", name), &canvas, corners);
+
+        algorithm.dispatch(Render { criterion, name, corners });
+    }
+}
+
+fn mapping(criterion: &mut Criterion) {
+    let algorithm = formula("escape_time");
+    let (upper_left, lower_right) = corners(&algorithm);
+    let mut group = criterion.benchmark_group("mapping");
+
+    group.bench_function("pixel_to_point", |bencher| {
+        bencher.iter(|| {
+            for row in 0..BOUNDS.1 {
+                for column in 0..BOUNDS.0 {
+                    black_box(pixel_to_point(BOUNDS, (column, row), upper_left, lower_right));
+                }
+            }
+        })
+    });
+    group.bench_function("row_points", |bencher| {
+        bencher.iter(|| {
+            for row in 0..BOUNDS.1 {
+                for point in row_points(BOUNDS, row, upper_left, lower_right) {
+                    black_box(point);
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = dispatch, mapping
+}
+criterion_main!(benches);
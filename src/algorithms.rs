@@ -1,53 +1,959 @@
+use crate::lanes;
 use num::{Complex, complex::ComplexFloat};
 
+/// A fractal formula: one step of its iteration and the test that ends its orbits, which the
+/// shared escape loop in `iterate` runs.
 pub trait PlottingAlgorithm {
-    fn calculate(&self, c: Complex<f64>, zoom: usize, limit: usize) -> Option<usize>;
+    /// The next value of `z` after `orbit.z`
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64>;
+
+    /// Whether the orbit ends at `orbit.z`, checked before every step
+    fn test(&self, orbit: &OrbitState, c: Complex<f64>) -> Outcome;
+
+    fn calculate(&self, c: Complex<f64>, _zoom: usize, limit: usize) -> Option<usize> {
+        self.orbit(c, limit, &mut |_| {})
+    }
+
+    /// `calculate` for every point of `points`, into `counts`. Formulas with a lane kernel
+    /// override it to iterate several points together; the rest take one point at a time.
+    fn calculate_batch(&self, points: &[Complex<f64>], limit: usize, counts: &mut [Option<usize>]) {
+        for (c, count) in points.iter().zip(counts) {
+            *count = self.calculate(*c, 1, limit);
+        }
+    }
+
+    /// Run the same iteration as `calculate`, handing every new value of `z` to `visitor`.
+    ///
+    /// The last point visited before an escape is the one that failed the bailout test.
+    fn orbit(&self, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor) -> Option<usize> {
+        self.orbit_from(self.critical_point(), c, limit, visitor)
+    }
+
+    /// Like `orbit`, but starting from `z0` instead of the critical point, as for Julia sets
+    fn orbit_from(
+        &self, z0: Complex<f64>, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        iterate(self, z0, c, limit, visitor)
+    }
+
+    /// The radius `z` must leave to escape, which smooth colorings interpolate against
+    fn escape_radius(&self) -> f64;
+
+    /// Where orbits of the parameter plane start: a critical point of the iterated function
+    fn critical_point(&self) -> Complex<f64> {
+        Complex::new(0.0, 0.0)
+    }
+
+    /// The zoom and center shown when the user gives neither
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (3.0, (-0.5, 0.0))
+    }
+}
+
+/// A formula behind a reference is the same formula, with every method passed through so that
+/// overrides such as `calculate_batch` still apply
+impl<A: PlottingAlgorithm + ?Sized> PlottingAlgorithm for &A {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        (**self).step(orbit, c)
+    }
+
+    fn test(&self, orbit: &OrbitState, c: Complex<f64>) -> Outcome {
+        (**self).test(orbit, c)
+    }
+
+    fn calculate(&self, c: Complex<f64>, zoom: usize, limit: usize) -> Option<usize> {
+        (**self).calculate(c, zoom, limit)
+    }
+
+    fn calculate_batch(&self, points: &[Complex<f64>], limit: usize, counts: &mut [Option<usize>]) {
+        (**self).calculate_batch(points, limit, counts)
+    }
+
+    fn orbit(
+        &self, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        (**self).orbit(c, limit, visitor)
+    }
+
+    fn orbit_from(
+        &self, z0: Complex<f64>, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        (**self).orbit_from(z0, c, limit, visitor)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        (**self).escape_radius()
+    }
+
+    fn critical_point(&self) -> Complex<f64> {
+        (**self).critical_point()
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (**self).default_view()
+    }
+}
+
+/// A boxed formula, passed through like a reference
+impl<A: PlottingAlgorithm + ?Sized> PlottingAlgorithm for Box<A> {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        (**self).step(orbit, c)
+    }
+
+    fn test(&self, orbit: &OrbitState, c: Complex<f64>) -> Outcome {
+        (**self).test(orbit, c)
+    }
+
+    fn calculate(&self, c: Complex<f64>, zoom: usize, limit: usize) -> Option<usize> {
+        (**self).calculate(c, zoom, limit)
+    }
+
+    fn calculate_batch(&self, points: &[Complex<f64>], limit: usize, counts: &mut [Option<usize>]) {
+        (**self).calculate_batch(points, limit, counts)
+    }
+
+    fn orbit(
+        &self, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        (**self).orbit(c, limit, visitor)
+    }
+
+    fn orbit_from(
+        &self, z0: Complex<f64>, c: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        (**self).orbit_from(z0, c, limit, visitor)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        (**self).escape_radius()
+    }
+
+    fn critical_point(&self) -> Complex<f64> {
+        (**self).critical_point()
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (**self).default_view()
+    }
+}
+
+/// Receives each point of an orbit as it is computed
+pub trait OrbitVisitor {
+    fn visit(&mut self, z: Complex<f64>);
+}
+
+impl<F: FnMut(Complex<f64>)> OrbitVisitor for F {
+    fn visit(&mut self, z: Complex<f64>) {
+        self(z)
+    }
+}
+
+/// How far an orbit has got
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitState {
+    pub z: Complex<f64>,
+    /// The point before `z`, taken to be 0 at the start
+    pub previous: Complex<f64>,
+    /// Steps taken to reach `z`
+    pub iteration: usize,
+    /// An earlier point of the orbit, moved up to `z` whenever `iteration` reaches a power of
+    /// two. An orbit caught in a cycle comes back to it once the gap between checkpoints is as
+    /// long as the cycle (Brent's method).
+    pub checkpoint: Complex<f64>,
+}
+
+impl OrbitState {
+    /// Whether the orbit has come back to within `tolerance` of its checkpoint
+    pub fn cycled(&self, tolerance: f64) -> bool {
+        self.iteration > 0 && (self.z - self.checkpoint).norm() <= tolerance
+    }
+}
+
+/// What a formula's test makes of the current point of an orbit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Keep iterating
+    Continue,
+    /// Stop, counting the point as outside the set
+    Escaped,
+    /// Stop, counting the point as inside the set
+    Captured,
+}
+
+/// The escape loop shared by every formula: step `algorithm` from `z0` until its test ends the
+/// orbit or `limit` steps have been taken, returning the iteration it escaped on
+pub fn iterate<A: PlottingAlgorithm + ?Sized>(
+    algorithm: &A, z0: Complex<f64>, c: Complex<f64>, limit: usize,
+    visitor: &mut dyn OrbitVisitor,
+) -> Option<usize> {
+    let mut orbit =
+        OrbitState { z: z0, previous: Complex::new(0.0, 0.0), iteration: 0, checkpoint: z0 };
+    while orbit.iteration < limit {
+        match algorithm.test(&orbit, c) {
+            Outcome::Continue => {}
+            Outcome::Escaped => return Some(orbit.iteration),
+            Outcome::Captured => return None,
+        }
+        let checkpoint = match orbit.iteration.is_power_of_two() {
+            true => orbit.z,
+            false => orbit.checkpoint,
+        };
+        let z = algorithm.step(&orbit, c);
+        orbit = OrbitState { z, previous: orbit.z, iteration: orbit.iteration + 1, checkpoint };
+        visitor.visit(z);
+    }
+    None
+}
+
+/// Radius used when none is given; orbits of the Mandelbrot set that leave it never return
+pub const DEFAULT_ESCAPE_RADIUS: f64 = 2.0;
+
+/// The radius `--escape-radius` defaults to for the algorithm called `name`. The Mandelbrot set,
+/// and the Multibrot sets drawn in its place, escape at `|z|² > 32` as they always have, which
+/// leaves the smooth colorings room to settle; the other formulas escape at
+/// `DEFAULT_ESCAPE_RADIUS`.
+pub fn default_escape_radius(name: &str) -> f64 {
+    match name {
+        "escape_time" => 32f64.sqrt(),
+        _ => DEFAULT_ESCAPE_RADIUS,
+    }
+}
+
+/// The shape of the region an orbit must leave to escape. Shapes other than a circle make
+/// decorative bands, as they cut the bands between iteration counts unevenly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bailout {
+    /// `|z| > r`
+    #[default]
+    Circle,
+    /// `max(|re z|, |im z|) > r`
+    Square,
+    /// `|re z| > r`
+    Real,
+    /// `|im z| > r`
+    Imaginary,
+    /// `|re z| + |im z| > r`
+    Manhattan,
+}
+
+impl Bailout {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "circle" => Ok(Bailout::Circle),
+            "square" => Ok(Bailout::Square),
+            "real" => Ok(Bailout::Real),
+            "imaginary" => Ok(Bailout::Imaginary),
+            "manhattan" => Ok(Bailout::Manhattan),
+            _ => Err(format!("unknown bailout '{}'", name)),
+        }
+    }
+}
+
+/// The bailout test an orbit is checked against on every iteration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Escape {
+    pub radius: f64,
+    pub bailout: Bailout,
+}
+
+impl Escape {
+    pub fn new(radius: f64, bailout: Bailout) -> Self {
+        Self { radius, bailout }
+    }
+
+    /// Whether `z` has escaped. Orbits that overflow count as escaped whatever the shape, since
+    /// the narrower tests could otherwise follow them to infinity.
+    pub fn escaped(&self, z: Complex<f64>) -> bool {
+        let (re, im) = (z.re.abs(), z.im.abs());
+        let outside = match self.bailout {
+            Bailout::Circle => z.norm_sqr() > self.radius * self.radius,
+            Bailout::Square => re.max(im) > self.radius,
+            Bailout::Real => re > self.radius,
+            Bailout::Imaginary => im > self.radius,
+            Bailout::Manhattan => re + im > self.radius,
+        };
+        outside || !z.norm_sqr().is_finite()
+    }
+
+    /// The test of formulas that have no other way of leaving
+    pub fn test(&self, z: Complex<f64>) -> Outcome {
+        match self.escaped(z) {
+            true => Outcome::Escaped,
+            false => Outcome::Continue,
+        }
+    }
+}
+
+impl Default for Escape {
+    fn default() -> Self {
+        Self::new(DEFAULT_ESCAPE_RADIUS, Bailout::Circle)
+    }
+}
+
+pub struct EscapeTime {
+    pub escape: Escape,
+}
+
+impl Default for EscapeTime {
+    fn default() -> Self {
+        Self { escape: Escape::new(default_escape_radius("escape_time"), Bailout::Circle) }
+    }
 }
 
-pub struct EscapeTime;
 impl PlottingAlgorithm for EscapeTime {
-    fn calculate(&self, c: Complex<f64>, _zoom: usize, limit: usize) -> Option<usize> {
-        // Set z = 0 (initial value of z)
-        let mut z = Complex { re: 0.0, im: 0.0 };
-        // Iterate up to the `limit` times
-        for i in 0..limit {
-            if z.norm_sqr() > 32.0 {
-                // Return the number of iterations it took to pass the check.
-                return Some(i);
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        orbit.z * orbit.z + c
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        self.escape.test(orbit.z)
+    }
+
+    fn calculate_batch(&self, points: &[Complex<f64>], limit: usize, counts: &mut [Option<usize>]) {
+        lanes::escape_time_batch(&self.escape, points, limit, counts)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius
+    }
+}
+
+/// The Burning Ship and its relatives, which fold `z` or `z²` into a quadrant with absolute
+/// values before adding `c`. Writing `z = x + iy` and `c = a + ib`:
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fold {
+    /// `(|x| + i|y|)² + c`: `x² - y² + a`, `2|xy| + b`
+    #[default]
+    BurningShip,
+    /// `|x² - y²| + a`, `2xy + b`
+    Celtic,
+    /// `x² - y² + a`, `-2x|y| + b`
+    PerpendicularBurningShip,
+    /// `|x² - y²| + a`, `2|xy| + b`
+    Buffalo,
+    /// `|x² - y²| + a`, `-2xy + b`
+    MandelbarCeltic,
+}
+
+impl Fold {
+    fn step(self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        let (x, y) = (z.re, z.im);
+        let (re, im) = match self {
+            Fold::BurningShip => (x * x - y * y, 2.0 * (x * y).abs()),
+            Fold::Celtic => ((x * x - y * y).abs(), 2.0 * x * y),
+            Fold::PerpendicularBurningShip => (x * x - y * y, -2.0 * x * y.abs()),
+            Fold::Buffalo => ((x * x - y * y).abs(), 2.0 * (x * y).abs()),
+            Fold::MandelbarCeltic => ((x * x - y * y).abs(), -2.0 * x * y),
+        };
+        Complex::new(re + c.re, im + c.im)
+    }
+}
+
+#[derive(Default)]
+pub struct BurningShip {
+    pub fold: Fold,
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for BurningShip {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        self.fold.step(orbit.z, c)
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        self.escape.test(orbit.z)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (3.5, (-0.45, -0.5))
+    }
+}
+
+/// The escape time algorithm generalized to `z^exponent + c`
+pub struct Multibrot {
+    pub exponent: f64,
+    pub escape: Escape,
+}
+
+impl Multibrot {
+    fn power(&self, z: Complex<f64>) -> Complex<f64> {
+        if self.exponent.fract() == 0.0 && self.exponent.abs() <= i32::MAX as f64 {
+            z.powi(self.exponent as i32)
+        } else if z.norm_sqr() == 0.0 {
+            z
+        } else {
+            z.powf(self.exponent)
+        }
+    }
+}
+
+impl PlottingAlgorithm for Multibrot {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        self.power(orbit.z) + c
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        self.escape.test(orbit.z)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (3.0, (0.0, 0.0))
+    }
+}
+
+/// `z_(n+1) = z_n² + c + p z_(n-1)`, which remembers the point before the current one
+pub struct Phoenix {
+    pub p: Complex<f64>,
+    pub escape: Escape,
+}
+
+/// The value of `p` used when none is given, which makes the classic Phoenix Julia set at
+/// `c = 0.5667`
+pub const DEFAULT_PHOENIX_P: Complex<f64> = Complex::new(-0.5, 0.0);
+
+impl PlottingAlgorithm for Phoenix {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        orbit.z * orbit.z + c + self.p * orbit.previous
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        self.escape.test(orbit.z)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (2.5, (-0.35, 0.0))
+    }
+}
+
+/// The two magnetic renormalization transformations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MagnetType {
+    /// `((z² + c - 1) / (2z + c - 2))²`
+    #[default]
+    One,
+    /// `((z³ + 3(c - 1)z + (c - 1)(c - 2)) / (3z² + 3(c - 2)z + (c - 1)(c - 2) + 1))²`
+    Two,
+}
+
+/// Orbits of the magnet formulas also settle on the fixed point at 1, which counts as escaping
+/// once they come this close to it
+const MAGNET_CONVERGENCE: f64 = 1e-6;
+
+/// Magnet orbits can wander well past 2 before falling back to 1, so they are only taken to
+/// escape beyond this radius, or the one given if larger
+const MAGNET_ESCAPE_RADIUS: f64 = 100.0;
+
+/// The magnet fractals from the renormalization of the Ising model, whose orbits either escape
+/// or converge to 1
+#[derive(Default)]
+pub struct Magnet {
+    pub kind: MagnetType,
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for Magnet {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        let z = orbit.z;
+        let ratio = match self.kind {
+            MagnetType::One => (z * z + c - 1.0) / (2.0 * z + c - 2.0),
+            MagnetType::Two => {
+                let (a, b) = (c - 1.0, c - 2.0);
+                (z * z * z + 3.0 * a * z + a * b) / (3.0 * z * z + 3.0 * b * z + a * b + 1.0)
             }
+        };
+        ratio * ratio
+    }
 
-            // update `z`
-            z = z * z + c;
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        let escape = Escape { radius: self.escape_radius(), ..self.escape };
+        if (orbit.z - 1.0).norm() < MAGNET_CONVERGENCE {
+            Outcome::Escaped
+        } else {
+            escape.test(orbit.z)
+        }
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius.max(MAGNET_ESCAPE_RADIUS)
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        match self.kind {
+            MagnetType::One => (8.0, (1.5, 0.0)),
+            MagnetType::Two => (6.0, (1.0, 0.0)),
         }
-        // If we have checked `limit` times without success, and z is still valid, return None
-        None
     }
 }
 
-pub struct BurningShip;
-impl PlottingAlgorithm for BurningShip {
-    fn calculate(&self, c: Complex<f64>, _zoom: usize, limit: usize) -> Option<usize> {
-        let mut z = Complex::new(0.0, 0.0);
-        let mut iterations = 0;
+/// The logistic map `λz(1 - z)`, with the parameter λ taken from the point
+#[derive(Default)]
+pub struct Lambda {
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for Lambda {
+    fn step(&self, orbit: &OrbitState, lambda: Complex<f64>) -> Complex<f64> {
+        lambda * orbit.z * (1.0 - orbit.z)
+    }
+
+    /// Once `|z| > 1 + 2/|λ|` the orbit grows on every step, so a point past the escape radius
+    /// is only taken to have escaped beyond that bound as well
+    fn test(&self, orbit: &OrbitState, lambda: Complex<f64>) -> Outcome {
+        let norm = orbit.z.norm();
+        let beyond = norm > 1.0 + 2.0 / lambda.norm() || !norm.is_finite();
+        match self.escape.escaped(orbit.z) && beyond {
+            true => Outcome::Escaped,
+            false => Outcome::Continue,
+        }
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius
+    }
+
+    fn critical_point(&self) -> Complex<f64> {
+        Complex::new(0.5, 0.0)
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (7.0, (1.0, 0.0))
+    }
+}
+
+/// Transcendental functions of `z` scaled by `c`, whose orbits run off to infinity along one
+/// axis rather than in every direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transcendental {
+    /// `c e^z`, which escapes once `re z > r`; far to the left `e^z` vanishes instead
+    #[default]
+    Exponential,
+    /// `c sin z`, which escapes once `|im z| > r`
+    Sine,
+    /// `c cos z`, which escapes once `|im z| > r`
+    Cosine,
+}
+
+/// Threshold of the transcendental escape tests, or of the one given if larger. The functions
+/// grow like `e^|z|`, so an orbit this far out overflows within a step or two.
+const TRANSCENDENTAL_THRESHOLD: f64 = 50.0;
+
+/// Escape time fractals of `c f(z)` for a transcendental `f`. The bailout shape doesn't apply:
+/// each function has its own test on the real or imaginary part.
+#[derive(Default)]
+pub struct TranscendentalMap {
+    pub function: Transcendental,
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for TranscendentalMap {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        match self.function {
+            Transcendental::Exponential => c * orbit.z.exp(),
+            Transcendental::Sine => c * orbit.z.sin(),
+            Transcendental::Cosine => c * orbit.z.cos(),
+        }
+    }
 
-        while z.norm_sqr() <= 4.0 && iterations < limit {
-            // Note the .abs() calls on the components
-            z = Complex::new(z.im.abs(), z.im.abs()); 
-            z = z * z + c;
-            iterations += 1;
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        let (z, threshold) = (orbit.z, self.escape_radius());
+        let outside = match self.function {
+            Transcendental::Exponential => z.re > threshold,
+            Transcendental::Sine | Transcendental::Cosine => z.im.abs() > threshold,
+        };
+        match outside || !z.norm_sqr().is_finite() {
+            true => Outcome::Escaped,
+            false => Outcome::Continue,
         }
-        if iterations == limit {
-            None
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius.max(TRANSCENDENTAL_THRESHOLD)
+    }
+
+    /// `e^z` has no critical point, but its asymptotic value 0 plays the same part. The critical
+    /// points of `sin` are at `π/2 + kπ`, and those of `cos` include 0.
+    fn critical_point(&self) -> Complex<f64> {
+        match self.function {
+            Transcendental::Sine => Complex::new(std::f64::consts::FRAC_PI_2, 0.0),
+            Transcendental::Exponential | Transcendental::Cosine => Complex::new(0.0, 0.0),
+        }
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        match self.function {
+            Transcendental::Exponential => (8.0, (-1.0, 0.0)),
+            Transcendental::Sine | Transcendental::Cosine => (10.0, (0.0, 0.0)),
+        }
+    }
+}
+
+/// Components of `z` must stay within this, or the given radius if larger, for a biomorph orbit
+/// to continue
+const BIOMORPH_THRESHOLD: f64 = 10.0;
+
+/// The value of `c` used for biomorphs when none is given, which grows Pickover's radiolarians
+pub const DEFAULT_BIOMORPH_C: Complex<f64> = Complex::new(0.5, 0.0);
+
+/// Pickover's biomorph formula `z³ + c`, with a bailout that tests each component on its own.
+/// Orbits stop once either component leaves the threshold, but only those that end with both
+/// outside count as escaped; the rest form the hairy "body" of the creature. The bailout shape
+/// doesn't apply. Biomorphs are Julia sets of this, for a fixed `c`.
+#[derive(Default)]
+pub struct Biomorph {
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for Biomorph {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        orbit.z * orbit.z * orbit.z + c
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        let threshold = self.escape_radius();
+        let (re, im) = (orbit.z.re.abs(), orbit.z.im.abs());
+        if re.min(im) > threshold {
+            Outcome::Escaped
+        } else if re > threshold || im > threshold || !orbit.z.norm_sqr().is_finite() {
+            Outcome::Captured
+        } else {
+            Outcome::Continue
+        }
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius.max(BIOMORPH_THRESHOLD)
+    }
+}
+
+/// How close an orbit must come back to its checkpoint to count as caught in a cycle
+const CYCLE_TOLERANCE: f64 = 1e-9;
+
+/// Orbits of the Collatz and tetration maps can wander far before settling, so they are only
+/// taken to escape beyond this, or the given radius if larger
+const DIVERGENCE_RADIUS: f64 = 1e6;
+
+/// The complex extension of the Collatz map, `(2 + 7z - (2 + 5z) cos(πz)) / 4`, which halves
+/// even integers and sends odd ones to `3z + 1`. It has no parameter, so each point is where
+/// its orbit starts. Orbits that escape, or that run off along the imaginary axis where
+/// `cos(πz)` grows exponentially, count as escaped; orbits that fall into a cycle, like
+/// `1, 4, 2, 1`, are captured.
+#[derive(Default)]
+pub struct Collatz {
+    pub escape: Escape,
+}
+
+/// Collatz orbits escape once `|im z|` passes this: `cos(πz)` then grows by a factor of about
+/// `e^(π |im z|)` per step
+const COLLATZ_IMAGINARY_THRESHOLD: f64 = 10.0;
+
+impl PlottingAlgorithm for Collatz {
+    fn step(&self, orbit: &OrbitState, _c: Complex<f64>) -> Complex<f64> {
+        let z = orbit.z;
+        (2.0 + 7.0 * z - (2.0 + 5.0 * z) * (std::f64::consts::PI * z).cos()) / 4.0
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        let escape = Escape { radius: self.escape_radius(), ..self.escape };
+        if escape.escaped(orbit.z) || orbit.z.im.abs() > COLLATZ_IMAGINARY_THRESHOLD {
+            Outcome::Escaped
+        } else if orbit.cycled(CYCLE_TOLERANCE) {
+            Outcome::Captured
         } else {
-            Some(iterations)
+            Outcome::Continue
         }
     }
+
+    /// The point passed as `c` is where the orbit starts, so `_z0` is unused
+    fn orbit_from(
+        &self, _z0: Complex<f64>, z: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        iterate(self, z, z, limit, visitor)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius.max(DIVERGENCE_RADIUS)
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (6.0, (1.0, 0.0))
+    }
 }
 
-pub fn get_plotting_algorithm(name: &str) -> Box<dyn PlottingAlgorithm + Send + Sync> {
-    match name {
-        "escape_time" => Box::new(EscapeTime),
-        "burning_ship" => Box::new(BurningShip),
-        _ => Box::new(EscapeTime), // default to EscapeTime if unknown
+/// The power tower `c^c^c^...`, iterating `z → c^z` with the principal logarithm of `c` from
+/// `z = 1`. Towers either diverge, which counts as escaping, or converge onto a cycle, which is
+/// detected so bounded orbits end early.
+#[derive(Default)]
+pub struct Tetration {
+    pub escape: Escape,
+}
+
+impl PlottingAlgorithm for Tetration {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        (orbit.z * c.ln()).exp()
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        let escape = Escape { radius: self.escape_radius(), ..self.escape };
+        if escape.escaped(orbit.z) {
+            Outcome::Escaped
+        } else if orbit.cycled(CYCLE_TOLERANCE) {
+            Outcome::Captured
+        } else {
+            Outcome::Continue
+        }
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.escape.radius.max(DIVERGENCE_RADIUS)
+    }
+
+    /// `c^z` has no critical point; its asymptotic value 0 leads to 1 on the first step
+    fn critical_point(&self) -> Complex<f64> {
+        Complex::new(1.0, 0.0)
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (8.0, (0.0, 0.0))
+    }
+}
+
+/// Formulas taken in turn, one per iteration, repeating the sequence for as long as the orbit
+/// runs. Each iteration is tested by the formula about to step.
+pub struct Hybrid {
+    pub formulas: Vec<Box<dyn PlottingAlgorithm + Send + Sync>>,
+}
+
+impl Hybrid {
+    /// Build a hybrid from a sequence of letters, one per iteration: `M` for the Mandelbrot set,
+    /// `B` Burning Ship, `C` Celtic, `P` Perpendicular Burning Ship, `F` Buffalo and `R`
+    /// Mandelbar Celtic. `MMBC` takes two Mandelbrot steps, then a Burning Ship and a Celtic one.
+    pub fn parse(sequence: &str, escape: Escape) -> Result<Self, String> {
+        let formulas = sequence
+            .chars()
+            .map(|letter| {
+                let name = match letter.to_ascii_uppercase() {
+                    'M' => "escape_time",
+                    'B' => "burning_ship",
+                    'C' => "celtic",
+                    'P' => "perpendicular_burning_ship",
+                    'F' => "buffalo",
+                    'R' => "mandelbar_celtic",
+                    _ => return Err(format!("unknown hybrid step '{}'", letter)),
+                };
+                get_plotting_algorithm(name, escape, &Parameters::default())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if formulas.is_empty() {
+            return Err("a hybrid needs at least one step".to_string());
+        }
+        Ok(Self { formulas })
+    }
+
+    fn formula(&self, orbit: &OrbitState) -> &(dyn PlottingAlgorithm + Send + Sync) {
+        self.formulas[orbit.iteration % self.formulas.len()].as_ref()
+    }
+}
+
+impl PlottingAlgorithm for Hybrid {
+    fn step(&self, orbit: &OrbitState, c: Complex<f64>) -> Complex<f64> {
+        self.formula(orbit).step(orbit, c)
+    }
+
+    fn test(&self, orbit: &OrbitState, c: Complex<f64>) -> Outcome {
+        self.formula(orbit).test(orbit, c)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.formulas.iter().map(|formula| formula.escape_radius()).fold(0.0, f64::max)
+    }
+
+    fn critical_point(&self) -> Complex<f64> {
+        self.formulas[0].critical_point()
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (4.0, (-0.5, 0.0))
+    }
+}
+
+/// The Julia set of another algorithm for a fixed `c`, where each point is the starting `z`
+pub struct Julia {
+    pub algorithm: Box<dyn PlottingAlgorithm + Send + Sync>,
+    pub c: Complex<f64>,
+}
+
+impl PlottingAlgorithm for Julia {
+    fn step(&self, orbit: &OrbitState, _c: Complex<f64>) -> Complex<f64> {
+        self.algorithm.step(orbit, self.c)
+    }
+
+    fn test(&self, orbit: &OrbitState, _c: Complex<f64>) -> Outcome {
+        self.algorithm.test(orbit, self.c)
+    }
+
+    /// The point passed as `c` is where the orbit starts, so `_z0` is unused
+    fn orbit_from(
+        &self, _z0: Complex<f64>, z: Complex<f64>, limit: usize, visitor: &mut dyn OrbitVisitor,
+    ) -> Option<usize> {
+        iterate(self, z, self.c, limit, visitor)
+    }
+
+    fn escape_radius(&self) -> f64 {
+        self.algorithm.escape_radius()
+    }
+
+    fn default_view(&self) -> (f64, (f64, f64)) {
+        (4.0, (0.0, 0.0))
+    }
+}
+
+/// The value of `--hybrid` used when none is given: Mandelbrot and Burning Ship steps in turn
+pub const DEFAULT_HYBRID: &str = "MB";
+
+/// Settings of the algorithms that have more than an escape test
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameters {
+    /// Weight of the previous point in the Phoenix formula
+    pub phoenix_p: Complex<f64>,
+    /// The constant added by the biomorph formula
+    pub biomorph_c: Complex<f64>,
+    /// Letters of the formulas a hybrid takes in turn, as accepted by `Hybrid::parse`
+    pub hybrid: String,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            phoenix_p: DEFAULT_PHOENIX_P,
+            biomorph_c: DEFAULT_BIOMORPH_C,
+            hybrid: DEFAULT_HYBRID.to_string(),
+        }
+    }
+}
+
+/// A formula chosen by name at run time. Rendering matches on it once, in `dispatch`, and then
+/// runs loops compiled for the formula inside rather than calling through a
+/// `dyn PlottingAlgorithm` for every pixel.
+pub enum Algorithm {
+    EscapeTime(EscapeTime),
+    BurningShip(BurningShip),
+    Phoenix(Phoenix),
+    Magnet(Magnet),
+    Lambda(Lambda),
+    Transcendental(TranscendentalMap),
+    Collatz(Collatz),
+    Tetration(Tetration),
+    /// Formulas built from boxed parts, biomorphs and hybrids, which call through to them anyway
+    Composite(Box<dyn PlottingAlgorithm + Send + Sync>),
+}
+
+/// Work done with a formula whose type is known at compile time; see `Algorithm::dispatch`
+pub trait WithAlgorithm {
+    type Output;
+
+    fn run<A: PlottingAlgorithm + Send + Sync + ?Sized>(self, algorithm: &A) -> Self::Output;
+}
+
+impl Algorithm {
+    /// The formula called `name`, falling back to `EscapeTime` for unknown names. A hybrid
+    /// whose steps don't parse is an error.
+    pub fn new(name: &str, escape: Escape, parameters: &Parameters) -> Result<Self, String> {
+        Ok(match name {
+            "escape_time" => Self::EscapeTime(EscapeTime { escape }),
+            "burning_ship" => Self::BurningShip(BurningShip { fold: Fold::BurningShip, escape }),
+            "celtic" => Self::BurningShip(BurningShip { fold: Fold::Celtic, escape }),
+            "perpendicular_burning_ship" => {
+                Self::BurningShip(BurningShip { fold: Fold::PerpendicularBurningShip, escape })
+            }
+            "buffalo" => Self::BurningShip(BurningShip { fold: Fold::Buffalo, escape }),
+            "mandelbar_celtic" => {
+                Self::BurningShip(BurningShip { fold: Fold::MandelbarCeltic, escape })
+            }
+            "phoenix" => Self::Phoenix(Phoenix { p: parameters.phoenix_p, escape }),
+            "magnet1" => Self::Magnet(Magnet { kind: MagnetType::One, escape }),
+            "magnet2" => Self::Magnet(Magnet { kind: MagnetType::Two, escape }),
+            "lambda" => Self::Lambda(Lambda { escape }),
+            "exponential" => Self::Transcendental(TranscendentalMap {
+                function: Transcendental::Exponential,
+                escape,
+            }),
+            "sine" => {
+                Self::Transcendental(TranscendentalMap { function: Transcendental::Sine, escape })
+            }
+            "cosine" => {
+                Self::Transcendental(TranscendentalMap { function: Transcendental::Cosine, escape })
+            }
+            "biomorph" => Self::Composite(Box::new(Julia {
+                algorithm: Box::new(Biomorph { escape }),
+                c: parameters.biomorph_c,
+            })),
+            "collatz" => Self::Collatz(Collatz { escape }),
+            "tetration" => Self::Tetration(Tetration { escape }),
+            "hybrid" => Self::Composite(Box::new(Hybrid::parse(&parameters.hybrid, escape)?)),
+            _ => Self::EscapeTime(EscapeTime { escape }), // default to EscapeTime if unknown
+        })
+    }
+
+    /// Run `work` with the formula inside, which is where the dispatch on the formula happens
+    pub fn dispatch<W: WithAlgorithm>(&self, work: W) -> W::Output {
+        match self {
+            Self::EscapeTime(algorithm) => work.run(algorithm),
+            Self::BurningShip(algorithm) => work.run(algorithm),
+            Self::Phoenix(algorithm) => work.run(algorithm),
+            Self::Magnet(algorithm) => work.run(algorithm),
+            Self::Lambda(algorithm) => work.run(algorithm),
+            Self::Transcendental(algorithm) => work.run(algorithm),
+            Self::Collatz(algorithm) => work.run(algorithm),
+            Self::Tetration(algorithm) => work.run(algorithm),
+            Self::Composite(algorithm) => work.run(algorithm.as_ref()),
+        }
     }
-}
\ No newline at end of file
+
+    /// The formula inside, for the odd call that doesn't need to be compiled for it
+    pub fn as_dyn(&self) -> &(dyn PlottingAlgorithm + Send + Sync) {
+        match self {
+            Self::EscapeTime(algorithm) => algorithm,
+            Self::BurningShip(algorithm) => algorithm,
+            Self::Phoenix(algorithm) => algorithm,
+            Self::Magnet(algorithm) => algorithm,
+            Self::Lambda(algorithm) => algorithm,
+            Self::Transcendental(algorithm) => algorithm,
+            Self::Collatz(algorithm) => algorithm,
+            Self::Tetration(algorithm) => algorithm,
+            Self::Composite(algorithm) => algorithm.as_ref(),
+        }
+    }
+
+    pub fn into_boxed(self) -> Box<dyn PlottingAlgorithm + Send + Sync> {
+        match self {
+            Self::EscapeTime(algorithm) => Box::new(algorithm),
+            Self::BurningShip(algorithm) => Box::new(algorithm),
+            Self::Phoenix(algorithm) => Box::new(algorithm),
+            Self::Magnet(algorithm) => Box::new(algorithm),
+            Self::Lambda(algorithm) => Box::new(algorithm),
+            Self::Transcendental(algorithm) => Box::new(algorithm),
+            Self::Collatz(algorithm) => Box::new(algorithm),
+            Self::Tetration(algorithm) => Box::new(algorithm),
+            Self::Composite(algorithm) => algorithm,
+        }
+    }
+}
+
+pub fn get_plotting_algorithm(
+    name: &str, escape: Escape, parameters: &Parameters,
+) -> Result<Box<dyn PlottingAlgorithm + Send + Sync>, String> {
+    Ok(Algorithm::new(name, escape, parameters)?.into_boxed())
+}
@@ -0,0 +1,261 @@
+use crate::mandelbrot::ComplexRegion;
+use num::complex::Complex;
+
+/// Newton steps allowed when locating a nucleus or Misiurewicz point
+const NEWTON_STEPS: usize = 64;
+
+/// How close the orbit of a Misiurewicz point must come back to itself to pass verification,
+/// and how far apart the points that must differ need to stay
+const VERIFY_TOLERANCE: f64 = 1e-9;
+
+/// Width of the view framing a component, relative to its size. The main cardioid has size 1,
+/// and the whole set fits a view about 3 wide.
+const FRAME_SCALE: f64 = 3.0;
+
+/// A hyperbolic component of the Mandelbrot set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Component {
+    /// The parameter whose critical orbit is exactly periodic
+    pub nucleus: Complex<f64>,
+    pub period: usize,
+    /// Size relative to the main cardioid
+    pub size: f64,
+    /// Angle in radians the component is turned by, relative to the main cardioid
+    pub orientation: f64,
+}
+
+impl Component {
+    /// Estimate the size and orientation of the component with the given nucleus
+    pub fn new(nucleus: Complex<f64>, period: usize) -> Self {
+        let (mut z, mut l, mut b) =
+            (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(1.0, 0.0));
+        for _ in 1..period {
+            z = z * z + nucleus;
+            l = 2.0 * z * l;
+            b += 1.0 / l;
+        }
+        let estimate = 1.0 / (b * l * l);
+
+        Self { nucleus, period, size: estimate.norm(), orientation: estimate.arg() }
+    }
+
+    /// The width of a view that frames the component, like the main set at zoom 3
+    pub fn frame_zoom(&self) -> f64 {
+        self.size * FRAME_SCALE
+    }
+}
+
+/// Find the lowest period, up to `limit`, of a component within `region`.
+///
+/// The corners of the region are iterated together; on the first iteration where the
+/// quadrilateral between their orbits surrounds the origin, some point of the region has
+/// returned to 0, which is the nucleus of a component with that period.
+pub fn find_period(region: ComplexRegion, limit: usize) -> Option<usize> {
+    let corners = [
+        region.upper_left,
+        Complex::new(region.lower_right.re, region.upper_left.im),
+        region.lower_right,
+        Complex::new(region.upper_left.re, region.lower_right.im),
+    ];
+    let mut orbits = [Complex::new(0.0, 0.0); 4];
+
+    for period in 1..=limit {
+        for (z, c) in orbits.iter_mut().zip(corners) {
+            *z = *z * *z + c;
+        }
+        if orbits.iter().any(|z| !z.norm_sqr().is_finite()) {
+            return None;
+        }
+        if surrounds_origin(&orbits) {
+            return Some(period);
+        }
+    }
+
+    None
+}
+
+/// Whether the polygon with the given vertices contains the origin, by counting crossings of
+/// the positive real axis
+fn surrounds_origin(polygon: &[Complex<f64>]) -> bool {
+    let mut inside = false;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if (a.im > 0.0) != (b.im > 0.0) {
+            let crossing = a.re - a.im * (b.re - a.re) / (b.im - a.im);
+            if crossing > 0.0 {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Locate the nucleus of period `period` nearest to `seed`, by solving `f^p(0) = 0` for `c`
+/// with Newton's method
+pub fn find_nucleus(seed: Complex<f64>, period: usize) -> Option<Complex<f64>> {
+    let mut c = seed;
+
+    for _ in 0..NEWTON_STEPS {
+        let (mut z, mut dc) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+        for _ in 0..period {
+            dc = 2.0 * z * dc + 1.0;
+            z = z * z + c;
+        }
+
+        let next = c - z / dc;
+        if !next.re.is_finite() || !next.im.is_finite() {
+            return None;
+        }
+        let moved = (next - c).norm();
+        c = next;
+        if moved <= f64::EPSILON * c.norm().max(f64::EPSILON) {
+            return Some(c);
+        }
+    }
+
+    // Newton's method may stall a rounding error away from the root
+    let z = (0..period).fold(Complex::new(0.0, 0.0), |z, _| z * z + c);
+    if z.norm() < 1e-9 {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+/// The critical orbit of `c` and its derivatives with respect to `c`, from `z_0 = 0` to
+/// `z_steps`
+fn critical_orbit(c: Complex<f64>, steps: usize) -> (Vec<Complex<f64>>, Vec<Complex<f64>>) {
+    let (mut z, mut dc) = (vec![Complex::new(0.0, 0.0)], vec![Complex::new(0.0, 0.0)]);
+    for k in 0..steps {
+        dc.push(2.0 * z[k] * dc[k] + 1.0);
+        z.push(z[k] * z[k] + c);
+    }
+    (z, dc)
+}
+
+/// Locate the Misiurewicz point nearest `seed` whose critical orbit becomes periodic with
+/// period `period` after `preperiod` steps, so `z_(preperiod + period) = z_preperiod`.
+///
+/// Newton's method is applied to that equation with the roots of lower preperiods divided out,
+/// which it would otherwise be drawn to.
+pub fn find_misiurewicz(
+    seed: Complex<f64>, preperiod: usize, period: usize,
+) -> Option<Complex<f64>> {
+    let mut c = seed;
+
+    for _ in 0..NEWTON_STEPS {
+        let (z, dc) = critical_orbit(c, preperiod + period);
+        let f = z[preperiod + period] - z[preperiod];
+        if f.norm_sqr() == 0.0 {
+            return Some(c);
+        }
+
+        // Newton's step for f / ∏ g_i is the reciprocal of f'/f - Σ g_i'/g_i
+        let mut ratio = (dc[preperiod + period] - dc[preperiod]) / f;
+        for i in 0..preperiod {
+            ratio -= (dc[i + period] - dc[i]) / (z[i + period] - z[i]);
+        }
+
+        let next = c - 1.0 / ratio;
+        if !next.re.is_finite() || !next.im.is_finite() {
+            return None;
+        }
+        let moved = (next - c).norm();
+        c = next;
+        if moved <= f64::EPSILON * c.norm().max(f64::EPSILON) {
+            return Some(c);
+        }
+    }
+
+    verify_misiurewicz(c, preperiod, period).ok().map(|_| c)
+}
+
+/// Check that `point` is a Misiurewicz point with exactly the given preperiod and period,
+/// returning how far its orbit misses returning to itself
+pub fn verify_misiurewicz(
+    point: Complex<f64>, preperiod: usize, period: usize,
+) -> Result<f64, String> {
+    let (z, _) = critical_orbit(point, preperiod + period);
+    let residual = (z[preperiod + period] - z[preperiod]).norm();
+    if residual > VERIFY_TOLERANCE {
+        return Err(format!("orbit misses its cycle by {:e}", residual));
+    }
+
+    for i in 0..preperiod {
+        if (z[i + period] - z[i]).norm() <= VERIFY_TOLERANCE {
+            return Err(format!("orbit is already periodic after {} steps", i));
+        }
+    }
+    for divisor in (1..period).filter(|&divisor| period.is_multiple_of(divisor)) {
+        if (z[preperiod + divisor] - z[preperiod]).norm() <= VERIFY_TOLERANCE {
+            return Err(format!("orbit has period {}", divisor));
+        }
+    }
+
+    Ok(residual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot::calculate_region;
+
+    const RABBIT: Complex<f64> = Complex::new(-0.12256116687665362, 0.7448617666197442);
+
+    #[test]
+    fn test_find_period() {
+        assert_eq!(find_period(calculate_region(0.1, (0.0, 0.0)), 100), Some(1));
+        assert_eq!(find_period(calculate_region(0.1, (-1.0, 0.0)), 100), Some(2));
+        assert_eq!(find_period(calculate_region(0.01, (-0.12, 0.74)), 100), Some(3));
+
+        // Far outside the set the corners escape
+        assert_eq!(find_period(calculate_region(0.1, (3.0, 3.0)), 100), None);
+    }
+
+    #[test]
+    fn test_find_nucleus() {
+        let nucleus = find_nucleus(Complex::new(-0.9, 0.1), 2).unwrap();
+        assert!((nucleus - Complex::new(-1.0, 0.0)).norm() < 1e-14);
+
+        let nucleus = find_nucleus(Complex::new(-0.12, 0.74), 3).unwrap();
+        assert!((nucleus - RABBIT).norm() < 1e-14);
+    }
+
+    #[test]
+    fn test_find_misiurewicz() {
+        // 0, -2, 2, 2, ...
+        let tip = find_misiurewicz(Complex::new(-1.9, 0.1), 2, 1).unwrap();
+        assert!((tip - Complex::new(-2.0, 0.0)).norm() < 1e-14);
+
+        // 0, i, -1 + i, -i, -1 + i, ...
+        let point = find_misiurewicz(Complex::new(0.1, 0.9), 2, 2).unwrap();
+        assert!((point - Complex::new(0.0, 1.0)).norm() < 1e-14);
+        assert!(verify_misiurewicz(point, 2, 2).is_ok());
+    }
+
+    #[test]
+    fn test_verify_misiurewicz() {
+        assert_eq!(verify_misiurewicz(Complex::new(-2.0, 0.0), 2, 1), Ok(0.0));
+        assert!(verify_misiurewicz(Complex::new(-1.9, 0.0), 2, 1).is_err());
+
+        // The nucleus at -1 is periodic from the start
+        assert!(verify_misiurewicz(Complex::new(-1.0, 0.0), 2, 2).is_err());
+        // -2 also repeats with period 2, but its true period is 1
+        assert!(verify_misiurewicz(Complex::new(-2.0, 0.0), 2, 2).is_err());
+    }
+
+    #[test]
+    fn test_component_size() {
+        let cardioid = Component::new(Complex::new(0.0, 0.0), 1);
+        assert_eq!((cardioid.size, cardioid.orientation), (1.0, 0.0));
+
+        let disk = Component::new(Complex::new(-1.0, 0.0), 2);
+        assert_eq!(disk.size, 0.5);
+        assert_eq!(disk.frame_zoom(), 1.5);
+
+        // The period 3 bulb on top of the main cardioid is much smaller, and turned
+        let rabbit = Component::new(RABBIT, 3);
+        assert!(rabbit.size > 0.05 && rabbit.size < 0.2);
+        assert!(rabbit.orientation.abs() > 0.1);
+    }
+}
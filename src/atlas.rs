@@ -0,0 +1,133 @@
+use crate::algorithms::{get_plotting_algorithm, Escape, Julia, Parameters};
+use crate::coloring::Coloring;
+use crate::mandelbrot::{calculate_corners, Canvas, ComplexRegion};
+use crate::montage::Montage;
+use crate::types::{Dimensions, Pixel};
+use num::complex::Complex;
+use rayon::prelude::*;
+
+/// Gray level of the Mandelbrot set drawn faintly behind the tiles
+const BACKDROP_LEVEL: u8 = 48;
+
+/// Gray level of the slowest escaping points outside of each Julia set
+const EXTERIOR_LEVEL: f64 = 160.0;
+
+/// A grid of Julia sets, each for the parameter at the center of its tile
+pub struct Atlas {
+    /// Name of the algorithm, as accepted by `get_plotting_algorithm`
+    pub algorithm: String,
+    pub escape: Escape,
+    pub parameters: Parameters,
+    /// Width of the dynamical plane shown in each tile, centered on the origin
+    pub julia_zoom: f64,
+    pub limit: usize,
+    /// Draw the set of parameters with connected Julia sets behind the tiles
+    pub backdrop: bool,
+}
+
+impl Atlas {
+    /// Render a `grid` of `tile` sized Julia sets sampled over `region` of the parameter plane
+    pub fn render(
+        &self, region: ComplexRegion, grid: Dimensions, tile: Dimensions,
+    ) -> Result<Montage, String> {
+        let (upper_left, lower_right) = calculate_corners(self.julia_zoom, (0.0, 0.0));
+        let tiles: Vec<Vec<u8>> = (0..grid.total_pixels())
+            .into_par_iter()
+            .map(|index| {
+                let c = tile_parameter(region, grid, index % grid.width, index / grid.width);
+                let algorithm =
+                    get_plotting_algorithm(&self.algorithm, self.escape, &self.parameters)?;
+                let julia = Julia { algorithm, c };
+                let canvas = Canvas::new(Box::new(julia), Coloring::Iterations);
+
+                let mut values = vec![None; tile.total_pixels()];
+                let bounds = (tile.width, tile.height);
+                canvas.render_iterations(&mut values, bounds, upper_left, lower_right, self.limit);
+
+                // The filled Julia set is drawn white, so its shape stands out at small sizes
+                Ok(values
+                    .iter()
+                    .map(|value| match value {
+                        None => u8::MAX,
+                        Some(count) => (count / self.limit as f64 * EXTERIOR_LEVEL) as u8,
+                    })
+                    .collect())
+            })
+            .collect::<Result<_, String>>()?;
+
+        let mut montage = Montage::new(grid, tile);
+        for (index, pixels) in tiles.iter().enumerate() {
+            montage.place(index % grid.width, index / grid.width, pixels);
+        }
+        if self.backdrop {
+            self.draw_backdrop(&mut montage, region)?;
+        }
+        Ok(montage)
+    }
+
+    /// Lighten the dark parts of the montage that fall inside the set in `region`
+    fn draw_backdrop(&self, montage: &mut Montage, region: ComplexRegion) -> Result<(), String> {
+        let bounds = montage.bounds();
+        let algorithm = get_plotting_algorithm(&self.algorithm, self.escape, &self.parameters)?;
+
+        montage.pixels.par_chunks_mut(bounds.width).enumerate().for_each(|(y, row)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let c = region.pixel_to_point(bounds, Pixel::new(x, y));
+                if algorithm.calculate(c, 1, self.limit).is_none() {
+                    *pixel = (*pixel).max(BACKDROP_LEVEL);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// The parameter at the center of the tile at `column`, `row` of a grid laid over `region`
+pub fn tile_parameter(
+    region: ComplexRegion, grid: Dimensions, column: usize, row: usize,
+) -> Complex<f64> {
+    let (width, height) = region.dimensions();
+    Complex::new(
+        region.upper_left.re + (column as f64 + 0.5) * width / grid.width as f64,
+        region.upper_left.im - (row as f64 + 0.5) * height / grid.height as f64,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot::calculate_region;
+
+    #[test]
+    fn test_tile_parameter() {
+        let region = calculate_region(4.0, (0.0, 0.0));
+        let grid = Dimensions::new(4, 2);
+        assert_eq!(tile_parameter(region, grid, 0, 0), Complex::new(-1.5, 1.0));
+        assert_eq!(tile_parameter(region, grid, 3, 1), Complex::new(1.5, -1.0));
+    }
+
+    #[test]
+    fn test_render_atlas() {
+        let atlas = Atlas {
+            algorithm: "escape_time".to_string(),
+            escape: Escape::default(),
+            parameters: Parameters::default(),
+            julia_zoom: 4.0,
+            limit: 50,
+            backdrop: false,
+        };
+        let region = calculate_region(0.1, (0.0, 0.0));
+        let tile = Dimensions::new(9, 9);
+        let montage = atlas.render(region, Dimensions::new(1, 1), tile).unwrap();
+
+        // The Julia set of 0 is the unit disk
+        assert_eq!(montage.pixels[4 * 9 + 4], u8::MAX);
+        assert!(montage.pixels[0] < BACKDROP_LEVEL);
+
+        // Which lies inside the Mandelbrot set
+        let atlas = Atlas { backdrop: true, ..atlas };
+        let montage = atlas.render(region, Dimensions::new(1, 1), tile).unwrap();
+        assert_eq!(montage.pixels[4 * 9 + 4], u8::MAX);
+        assert_eq!(montage.pixels[0], BACKDROP_LEVEL);
+    }
+}
@@ -1,24 +1,99 @@
-use clap::{Parser, builder::PossibleValuesParser};
+use clap::{Args, Parser, Subcommand, builder::PossibleValuesParser, builder::TypedValueParser};
 
-use crate::parsers::parse_pair;
+use crate::algorithms::{default_escape_radius, Bailout, Escape, Hybrid, Parameters};
+use num::Complex;
+use crate::parsers::{self, parse_list, parse_pair, parse_params};
+use crate::rays::ExternalAngle;
+use crate::traps::OrbitTrap;
 
 #[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    args_override_self = true,
+    after_help = "Arguments can also be read from a parameter file with `--params <FILE>`, which \
+        holds one `key = value` (or bare `flag`) per line. Arguments following it on the command \
+        line take precedence."
+)]
 pub struct Arguments {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub render: Option<RenderArguments>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Render an iterated function system or fractal flame from a definition file
+    Ifs(IfsArguments),
+    /// Locate the nucleus of the hyperbolic component (minibrot) or, with --preperiod, the
+    /// Misiurewicz point nearest a point
+    Analyze(AnalyzeArguments),
+    /// Dump the orbit of a single point as CSV or JSON
+    Orbit(OrbitArguments),
+    /// Render a grid of Julia sets for parameters sampled over a region of the Mandelbrot set
+    Atlas(AtlasArguments),
+    /// Render variants of one view with a parameter swept across them, as a labeled contact sheet
+    Sweep(SweepArguments),
+    /// Plot the boundary of a Julia set by inverse iteration, and test whether it is connected
+    Inverse(InverseArguments),
+    /// Ray march a Mandelbulb or quaternion Julia set into a shaded RGB image
+    Raymarch(RaymarchArguments),
+    /// Render the frames of a zoom video by resampling one exponential map strip
+    Zoom(ZoomArguments),
+}
+
+#[derive(Debug, Args)]
+pub struct RenderArguments {
+    /// Width of the rendered region; each algorithm has a default that shows all of it
     #[arg(short, long)]
-    pub zoom: f64,
+    pub zoom: Option<f64>,
 
     #[arg(short, long, default_value = "mandelbrot.png")]
     pub output: String,
 
     #[arg(
-        short, 
-        long, 
+        short,
+        long,
         default_value = "escape_time",
-        value_parser = PossibleValuesParser::new(["escape_time", "burning_ship"])
+        value_parser = PossibleValuesParser::new(ALGORITHMS)
     )]
     pub algorithm: String,
 
+    // Inlined rather than flattened `EscapeArguments` and `FormulaArguments`: clap can't tell
+    // whether an optional flattened group is present when it holds a nested one
+    /// Radius an orbit must leave to escape [default: √32 for escape_time, 2 for the others]
+    #[arg(long, value_parser = parse_escape_radius)]
+    pub escape_radius: Option<f64>,
+
+    /// Shape of the region orbits escape from
+    #[arg(
+        long,
+        default_value = "circle",
+        value_parser =
+            PossibleValuesParser::new(BAILOUTS).try_map(|name: String| Bailout::parse(&name))
+    )]
+    pub bailout: Bailout,
+
+    /// Weight p of the previous point in the Phoenix formula, as re,im
+    #[arg(long, default_value = "-0.5,0", allow_hyphen_values = true, value_parser = parse_complex)]
+    pub phoenix_p: Complex<f64>,
+
+    /// The constant c added by the biomorph formula z³ + c, as re,im
+    #[arg(long, default_value = "0.5,0", allow_hyphen_values = true, value_parser = parse_complex)]
+    pub biomorph_c: Complex<f64>,
+
+    /// Formulas the hybrid algorithm takes in turn, one letter per iteration: M (Mandelbrot),
+    /// B (Burning Ship), C (Celtic), P (Perpendicular Burning Ship), F (Buffalo) or
+    /// R (Mandelbar Celtic)
+    #[arg(long, default_value = "MB", value_parser = parse_hybrid)]
+    pub hybrid: String,
+
+    /// Center of the rendered region; each algorithm has a default that shows all of it
     #[arg(
         short,
         long,
@@ -28,7 +103,7 @@ pub struct Arguments {
             None => Err("error parsing center point".to_string())
         }
     )]
-    pub center: (f64, f64),
+    pub center: Option<(f64, f64)>,
 
     #[arg(
         short,
@@ -49,4 +124,922 @@ pub struct Arguments {
 
     #[arg(short, long)]
     pub invert: bool,
-}
\ No newline at end of file
+
+    /// How escaping orbits are shaded
+    #[arg(
+        long,
+        default_value = "iterations",
+        value_parser = PossibleValuesParser::new([
+            "iterations", "smooth", "triangle", "stripe", "curvature", "field-lines"
+        ])
+    )]
+    pub coloring: String,
+
+    /// Number of stripes per turn for stripe average coloring
+    #[arg(long, default_value = "5.0")]
+    pub stripe_density: f64,
+
+    /// Number of field lines per turn for field line coloring, which only applies to the
+    /// escape_time algorithm
+    #[arg(long, default_value = "16.0")]
+    pub field_line_density: f64,
+
+    /// Highlight the external ray at this angle, a fraction of a turn like 1/3, in field line
+    /// coloring; may be repeated
+    #[arg(long, value_parser = ExternalAngle::parse)]
+    pub external_angle: Vec<ExternalAngle>,
+
+    /// Trace the external ray of the Mandelbrot set at this angle, like 1/7, and draw it over the
+    /// image; may be repeated. Only for the escape_time algorithm
+    #[arg(long, value_parser = ExternalAngle::parse)]
+    pub trace_ray: Vec<ExternalAngle>,
+
+    /// Number of dwell bands a traced ray crosses on its way in
+    #[arg(long, default_value = "50")]
+    pub ray_depth: usize,
+
+    /// Color by the orbit's closest approach to a trap: point:x,y, line:x,y,degrees, cross:x,y,
+    /// circle:x,y,radius or image:path,x,y,size
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["coloring", "decomposition", "decomposition_texture"],
+        value_parser = OrbitTrap::parse
+    )]
+    pub trap: Option<OrbitTrap>,
+
+    /// Split iteration bands by the argument of the escaped point
+    #[arg(long, value_parser = PossibleValuesParser::new(["binary", "angular"]))]
+    pub decomposition: Option<String>,
+
+    /// Tile this image over the exterior, using angular decomposition and the smooth iteration
+    #[arg(long, conflicts_with = "decomposition")]
+    pub decomposition_texture: Option<String>,
+
+    /// Equalize the histogram of iteration counts, so every gray level covers as many pixels
+    #[arg(long, conflicts_with_all = ["trap", "decomposition", "decomposition_texture"])]
+    pub histogram: bool,
+
+    /// Leave interior points out of the histogram
+    #[arg(long, requires = "histogram")]
+    pub exclude_interior: bool,
+
+    /// Weight of the linear mapping blended into the equalized one, from 0 to 1
+    #[arg(long, default_value = "0.0", requires = "histogram", value_parser = parse_fraction)]
+    pub histogram_blend: f64,
+
+    /// File carrying the histogram between frames of an animation; read if present, then updated
+    #[arg(long, requires = "histogram")]
+    pub histogram_state: Option<String>,
+
+    /// Share of the previous frame's histogram kept when stabilizing with --histogram-state
+    #[arg(long, default_value = "0.8", requires = "histogram_state", value_parser = parse_fraction)]
+    pub histogram_momentum: f64,
+
+    /// Distance over which trap brightness decays
+    #[arg(long, default_value = "0.1")]
+    pub trap_falloff: f64,
+
+    /// Also export the render as a heightmap mesh, in the format given by the extension: .obj,
+    /// .stl or .ply
+    #[arg(long)]
+    pub mesh: Option<String>,
+
+    /// The field the mesh is raised from: the smooth iteration count or the distance estimate
+    #[arg(
+        long,
+        default_value = "smooth",
+        requires = "mesh",
+        value_parser = PossibleValuesParser::new(["smooth", "distance"])
+    )]
+    pub mesh_field: String,
+
+    /// Height of the top of the mesh above its lowest point, in pixel widths
+    #[arg(long, default_value = "50.0", requires = "mesh")]
+    pub height_scale: f64,
+
+    /// Range of the field mapped onto the height of the mesh; the whole range when omitted
+    #[arg(
+        long,
+        requires = "mesh",
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing height clamp".to_string())
+        }
+    )]
+    pub height_clamp: Option<(f64, f64)>,
+
+    /// Cover flat stretches of the mesh, like the interior, with a few large triangles
+    #[arg(long, requires = "mesh")]
+    pub decimate: bool,
+
+    /// Close the mesh into a solid with a base this thick, in pixel widths
+    #[arg(long, requires = "mesh")]
+    pub mesh_base: Option<f64>,
+
+    /// Also export the contours of the iteration bands as SVG polygons
+    #[arg(long)]
+    pub svg: Option<String>,
+
+    /// Iterations spanned by each band of the SVG
+    #[arg(long, default_value = "1", requires = "svg")]
+    pub band_width: usize,
+
+    /// Draw outlines at these smooth iteration counts, comma separated, rather than bands
+    #[arg(long, value_delimiter = ',', requires = "svg")]
+    pub isolines: Vec<f64>,
+
+    /// How far contours may be straightened, in pixels
+    #[arg(long, default_value = "0.5", requires = "svg")]
+    pub simplify: f64,
+
+    /// Smallest area a contour may enclose, in square pixels; smaller ones are left out
+    #[arg(long, default_value = "4.0", requires = "svg")]
+    pub min_contour_area: f64,
+
+    /// How pixels map to the plane: `linear`, `rotated` by --rotation, `inversion` (1/w),
+    /// `mobius` by --mobius, `sphere` (the Riemann sphere as a disk) or `equirectangular` (a 360°
+    /// panorama of the sphere)
+    #[arg(long, default_value = "linear", value_parser = PossibleValuesParser::new(PROJECTIONS))]
+    pub projection: String,
+
+    /// Counterclockwise turn of the view, in degrees; around the poles for sphere projections
+    #[arg(long, default_value = "0.0", allow_hyphen_values = true)]
+    pub rotation: f64,
+
+    /// Tilt of the sphere projections, in degrees, bringing infinity up towards the viewer
+    #[arg(long, default_value = "0.0", allow_hyphen_values = true)]
+    pub pitch: f64,
+
+    /// Coefficients a, b, c and d of the Möbius transform (a w + b) / (c w + d), as eight
+    /// numbers: the real and imaginary parts of each in turn
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        required_if_eq("projection", "mobius"),
+        value_parser = |arg: &str| match parse_list::<f64>(arg, ',', 8) {
+            Some(v) => Ok([0, 2, 4, 6].map(|i| Complex::new(v[i], v[i + 1]))),
+            None => Err("expected eight comma separated numbers".to_string())
+        }
+    )]
+    pub mobius: Option<[Complex<f64>; 4]>,
+}
+
+#[derive(Debug, Args)]
+pub struct IfsArguments {
+    /// File with one affine transform per line: `a b c d e f weight [color=..] [variation=..]`
+    #[arg(short = 'f', long)]
+    pub definition: String,
+
+    #[arg(short, long, default_value = "ifs.png")]
+    pub output: String,
+
+    /// Width of the rendered region; fitted to the attractor when omitted
+    #[arg(short, long)]
+    pub zoom: Option<f64>,
+
+    /// Center of the rendered region; fitted to the attractor when omitted
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: Option<(f64, f64)>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1920x1080",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing image dimensions".to_string())
+        }
+    )]
+    pub dimensions: (usize, usize),
+
+    /// Number of points plotted by the chaos game
+    #[arg(short = 'n', long, default_value = "10000000")]
+    pub iterations: usize,
+
+    /// Render as a colored fractal flame with log-density brightness
+    #[arg(long)]
+    pub flame: bool,
+
+    /// Gamma correction applied to flame brightness
+    #[arg(long, default_value = "2.2")]
+    pub gamma: f64,
+}
+
+#[derive(Debug, Args)]
+pub struct AnalyzeArguments {
+    /// Point to start searching from
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: (f64, f64),
+
+    /// Width of the region searched for the lowest period, centered on the point
+    #[arg(short, long, required_unless_present = "period")]
+    pub zoom: Option<f64>,
+
+    /// Period of the component; detected over the region when omitted
+    #[arg(short, long)]
+    pub period: Option<usize>,
+
+    /// Find a Misiurewicz point, whose orbit becomes periodic after this many steps, instead of
+    /// a nucleus
+    #[arg(long, requires = "period")]
+    pub preperiod: Option<usize>,
+
+    /// Highest period considered when detecting the period
+    #[arg(short, long, default_value = "10000")]
+    pub limit: usize,
+
+    /// Also print --center and --zoom arguments that frame the component
+    #[arg(long)]
+    pub frame: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct OrbitArguments {
+    /// The parameter c to iterate with
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing point".to_string())
+        }
+    )]
+    pub point: (f64, f64),
+
+    /// Starting value of z, for the orbit of a point in the Julia set of c; 0 when omitted
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing z0".to_string())
+        }
+    )]
+    pub z0: Option<(f64, f64)>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "escape_time",
+        value_parser = PossibleValuesParser::new(ALGORITHMS)
+    )]
+    pub algorithm: String,
+
+    #[command(flatten)]
+    pub escape: EscapeArguments,
+
+    #[command(flatten)]
+    pub formula: FormulaArguments,
+
+    #[arg(short, long, default_value = "1000")]
+    pub limit: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value = "csv",
+        value_parser = PossibleValuesParser::new(["csv", "json"])
+    )]
+    pub format: String,
+
+    /// File to write the orbit to; printed when omitted
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Also render the Mandelbrot set (or Julia set, with --z0) to this PNG, with the orbit drawn
+    /// over it
+    #[arg(long)]
+    pub render: Option<String>,
+
+    /// Width of the rendered region
+    #[arg(short, long, default_value = "4.0", requires = "render")]
+    pub zoom: f64,
+
+    /// Center of the rendered region
+    #[arg(
+        short,
+        long,
+        default_value = "0,0",
+        allow_hyphen_values = true,
+        requires = "render",
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: (f64, f64),
+
+    #[arg(
+        short,
+        long,
+        default_value = "800x800",
+        requires = "render",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing image dimensions".to_string())
+        }
+    )]
+    pub dimensions: (usize, usize),
+}
+
+#[derive(Debug, Args)]
+pub struct AtlasArguments {
+    /// Center of the region of the parameter plane sampled
+    #[arg(
+        short,
+        long,
+        default_value = "-0.5,0",
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: (f64, f64),
+
+    /// Width of the region of the parameter plane sampled
+    #[arg(short, long, default_value = "3.0")]
+    pub zoom: f64,
+
+    /// Number of tiles across and down
+    #[arg(
+        long,
+        default_value = "8x8",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing grid size".to_string())
+        }
+    )]
+    pub grid: (usize, usize),
+
+    /// Resolution of each tile
+    #[arg(
+        long,
+        default_value = "128x128",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing tile dimensions".to_string())
+        }
+    )]
+    pub tile: (usize, usize),
+
+    /// Width of the dynamical plane shown in each tile
+    #[arg(long, default_value = "4.0")]
+    pub julia_zoom: f64,
+
+    #[arg(
+        short,
+        long,
+        default_value = "escape_time",
+        value_parser = PossibleValuesParser::new(ALGORITHMS)
+    )]
+    pub algorithm: String,
+
+    #[command(flatten)]
+    pub escape: EscapeArguments,
+
+    #[command(flatten)]
+    pub formula: FormulaArguments,
+
+    #[arg(short, long, default_value = "200")]
+    pub limit: usize,
+
+    /// Draw the Mandelbrot set faintly behind the tiles
+    #[arg(long)]
+    pub backdrop: bool,
+
+    #[arg(short, long, default_value = "atlas.png")]
+    pub output: String,
+}
+
+#[derive(Debug, Args)]
+pub struct SweepArguments {
+    /// The parameter varied from tile to tile
+    #[arg(
+        short,
+        long,
+        value_parser = PossibleValuesParser::new(["limit", "exponent", "radius", "julia"])
+    )]
+    pub parameter: String,
+
+    /// First value of a limit, exponent or radius sweep
+    #[arg(long, allow_hyphen_values = true)]
+    pub from: Option<f64>,
+
+    /// Last value of a limit, exponent or radius sweep
+    #[arg(long, allow_hyphen_values = true)]
+    pub to: Option<f64>,
+
+    /// A corner of the path that a Julia sweep moves c along; give at least two, in order
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing path point".to_string())
+        }
+    )]
+    pub path_point: Vec<(f64, f64)>,
+
+    /// Number of tiles
+    #[arg(short = 'n', long, default_value = "9")]
+    pub steps: usize,
+
+    /// Number of tiles across the contact sheet
+    #[arg(long, default_value = "3")]
+    pub columns: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value = "0,0",
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: (f64, f64),
+
+    #[arg(short, long, default_value = "4.0")]
+    pub zoom: f64,
+
+    /// Iteration limit of tiles that don't sweep it
+    #[arg(short, long, default_value = "200")]
+    pub limit: usize,
+
+    /// Exponent d of z^d + c in tiles that don't sweep it, for the escape_time algorithm only
+    #[arg(long, default_value = "2.0")]
+    pub exponent: f64,
+
+    #[arg(
+        short,
+        long,
+        default_value = "escape_time",
+        value_parser = PossibleValuesParser::new(ALGORITHMS)
+    )]
+    pub algorithm: String,
+
+    #[command(flatten)]
+    pub escape: EscapeArguments,
+
+    #[command(flatten)]
+    pub formula: FormulaArguments,
+
+    #[arg(
+        long,
+        default_value = "iterations",
+        value_parser = PossibleValuesParser::new(["iterations", "smooth"])
+    )]
+    pub coloring: String,
+
+    /// Resolution of each tile
+    #[arg(
+        long,
+        default_value = "256x256",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing tile dimensions".to_string())
+        }
+    )]
+    pub tile: (usize, usize),
+
+    #[arg(short, long, default_value = "sweep.png")]
+    pub output: String,
+}
+
+#[derive(Debug, Args)]
+pub struct InverseArguments {
+    /// The parameter c of the Julia set of z² + c
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing point".to_string())
+        }
+    )]
+    pub point: (f64, f64),
+
+    #[arg(short, long, default_value = "4.0")]
+    pub zoom: f64,
+
+    #[arg(
+        short,
+        long,
+        default_value = "0,0",
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: (f64, f64),
+
+    #[arg(
+        short,
+        long,
+        default_value = "1920x1080",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing image dimensions".to_string())
+        }
+    )]
+    pub dimensions: (usize, usize),
+
+    /// Visits allowed per pixel before the preimages through it are no longer followed
+    #[arg(long, default_value = "4")]
+    pub max_hits: u32,
+
+    /// Upper bound on the points visited
+    #[arg(long, default_value = "50000000")]
+    pub max_points: usize,
+
+    /// Iterations of the critical orbit checked by the connectedness test
+    #[arg(short, long, default_value = "1000")]
+    pub limit: usize,
+
+    #[arg(short, long, default_value = "julia.png")]
+    pub output: String,
+}
+
+/// How orbits are tested for escape, shared by everything that renders
+#[derive(Debug, Args)]
+pub struct EscapeArguments {
+    /// Radius an orbit must leave to escape [default: √32 for escape_time, 2 for the others]
+    #[arg(long, value_parser = parse_escape_radius)]
+    pub escape_radius: Option<f64>,
+
+    /// Shape of the region orbits escape from
+    #[arg(
+        long,
+        default_value = "circle",
+        value_parser =
+            PossibleValuesParser::new(BAILOUTS).try_map(|name: String| Bailout::parse(&name))
+    )]
+    pub bailout: Bailout,
+}
+
+impl EscapeArguments {
+    /// The escape test for `algorithm`, at its own default radius unless one was given
+    pub fn escape(&self, algorithm: &str) -> Escape {
+        let radius = self.escape_radius.unwrap_or_else(|| default_escape_radius(algorithm));
+        Escape::new(radius, self.bailout)
+    }
+}
+
+/// Settings of the formulas that take more than a point, shared by everything that renders
+#[derive(Debug, Args)]
+pub struct FormulaArguments {
+    /// Weight p of the previous point in the Phoenix formula, as re,im
+    #[arg(long, default_value = "-0.5,0", allow_hyphen_values = true, value_parser = parse_complex)]
+    pub phoenix_p: Complex<f64>,
+
+    /// The constant c added by the biomorph formula z³ + c, as re,im
+    #[arg(long, default_value = "0.5,0", allow_hyphen_values = true, value_parser = parse_complex)]
+    pub biomorph_c: Complex<f64>,
+
+    /// Formulas the hybrid algorithm takes in turn, one letter per iteration: M (Mandelbrot),
+    /// B (Burning Ship), C (Celtic), P (Perpendicular Burning Ship), F (Buffalo) or
+    /// R (Mandelbar Celtic)
+    #[arg(long, default_value = "MB", value_parser = parse_hybrid)]
+    pub hybrid: String,
+}
+
+impl FormulaArguments {
+    pub fn parameters(&self) -> Parameters {
+        Parameters {
+            phoenix_p: self.phoenix_p,
+            biomorph_c: self.biomorph_c,
+            hybrid: self.hybrid.clone(),
+        }
+    }
+}
+
+// The inlined fields are gathered back into their groups, so each group's methods stay the one
+// place its settings are turned into what the formulas take
+impl RenderArguments {
+    pub fn escape(&self) -> Escape {
+        EscapeArguments { escape_radius: self.escape_radius, bailout: self.bailout }
+            .escape(&self.algorithm)
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        FormulaArguments {
+            phoenix_p: self.phoenix_p,
+            biomorph_c: self.biomorph_c,
+            hybrid: self.hybrid.clone(),
+        }
+        .parameters()
+    }
+}
+
+/// Names of the algorithms accepted by `get_plotting_algorithm`
+const ALGORITHMS: [&str; 17] = [
+    "escape_time",
+    "burning_ship",
+    "celtic",
+    "perpendicular_burning_ship",
+    "buffalo",
+    "mandelbar_celtic",
+    "phoenix",
+    "magnet1",
+    "magnet2",
+    "lambda",
+    "exponential",
+    "sine",
+    "cosine",
+    "biomorph",
+    "hybrid",
+    "collatz",
+    "tetration",
+];
+
+/// Names of the bailout shapes accepted by `Bailout::parse`
+const BAILOUTS: [&str; 5] = ["circle", "square", "real", "imaginary", "manhattan"];
+
+/// Names of the projections accepted by `--projection`
+const PROJECTIONS: [&str; 6] =
+    ["linear", "rotated", "inversion", "mobius", "sphere", "equirectangular"];
+
+/// `parsers::parse_complex`, with the error clap reports
+fn parse_complex(arg: &str) -> Result<Complex<f64>, String> {
+    parsers::parse_complex(arg).ok_or_else(|| "expected a complex number as re,im".to_string())
+}
+
+fn parse_hybrid(arg: &str) -> Result<String, String> {
+    Hybrid::parse(arg, Escape::default()).map(|_| arg.to_string())
+}
+
+fn parse_escape_radius(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(radius) if radius > 1.0 => Ok(radius),
+        _ => Err("escape radius must be a number greater than 1".to_string()),
+    }
+}
+
+/// Parse a weight between 0 and 1
+fn parse_fraction(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(weight) if (0.0..=1.0).contains(&weight) => Ok(weight),
+        _ => Err("expected a number from 0 to 1".to_string()),
+    }
+}
+
+/// Parse the width of the region a zoom frame shows
+fn parse_zoom_width(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(width) if width > 0.0 && width.is_finite() => Ok(width),
+        _ => Err("zoom width must be a positive number".to_string()),
+    }
+}
+
+impl Arguments {
+    /// Parse the process arguments, expanding any `--params <FILE>` in place
+    pub fn parse_with_params() -> Self {
+        match expand_params(std::env::args()) {
+            Ok(args) => Self::parse_from(args),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// Replace each `--params <FILE>` (or `--params=<FILE>`) with the arguments listed in the file
+fn expand_params(args: impl IntoIterator<Item = String>) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let path = if arg == "--params" {
+            args.next().ok_or("--params requires a file name")?
+        } else if let Some(path) = arg.strip_prefix("--params=") {
+            path.to_string()
+        } else {
+            expanded.push(arg);
+            continue;
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("error reading parameter file {}: {}", path, err))?;
+        expanded.extend(parse_params(&contents).map_err(|err| format!("{}: {}", path, err))?);
+    }
+
+    Ok(expanded)
+}
+
+/// Parse a point or direction in space, like `0,0,-3`
+fn parse_vector(arg: &str) -> Result<[f64; 3], String> {
+    match parse_list::<f64>(arg, ',', 3) {
+        Some(v) => Ok([v[0], v[1], v[2]]),
+        None => Err("expected three comma separated numbers".to_string()),
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RaymarchArguments {
+    /// The fractal to render
+    #[arg(
+        short,
+        long,
+        default_value = "mandelbulb",
+        value_parser = PossibleValuesParser::new(["mandelbulb", "quaternion"])
+    )]
+    pub shape: String,
+
+    /// Power of the Mandelbulb
+    #[arg(long, default_value = "8.0")]
+    pub power: f64,
+
+    /// The parameter c of the quaternion Julia set of q² + c, as its four components
+    #[arg(
+        long,
+        default_value = "-0.2,0.8,0,0",
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_list::<f64>(arg, ',', 4) {
+            Some(v) => Ok([v[0], v[1], v[2], v[3]]),
+            None => Err("expected four comma separated numbers".to_string())
+        }
+    )]
+    pub julia_c: [f64; 4],
+
+    /// Iterations of the distance estimator
+    #[arg(short, long, default_value = "12")]
+    pub iterations: usize,
+
+    /// Position of the camera
+    #[arg(long, default_value = "0,0,-3", allow_hyphen_values = true, value_parser = parse_vector)]
+    pub camera: [f64; 3],
+
+    /// Point the camera looks at
+    #[arg(long, default_value = "0,0,0", allow_hyphen_values = true, value_parser = parse_vector)]
+    pub target: [f64; 3],
+
+    /// Horizontal field of view, in degrees, between 0 and 180
+    #[arg(long, default_value = "45.0")]
+    pub fov: f64,
+
+    /// Steps a ray may take before it is taken to have missed
+    #[arg(long, default_value = "256")]
+    pub max_steps: usize,
+
+    /// Sharpness of shadow edges; 0 turns shadows off
+    #[arg(long, default_value = "16.0")]
+    pub shadow_softness: f64,
+
+    /// Darkening of creases by ambient occlusion; 0 turns it off
+    #[arg(long, default_value = "1.0")]
+    pub ao_strength: f64,
+
+    #[arg(
+        short,
+        long,
+        default_value = "800x600",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing image dimensions".to_string())
+        }
+    )]
+    pub dimensions: (usize, usize),
+
+    #[arg(short, long, default_value = "raymarch.png")]
+    pub output: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ZoomArguments {
+    /// Point the zoom closes in on; each algorithm has a default
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        value_parser = |arg: &str| match parse_pair::<f64>(arg, ',') {
+            Some(v) => Ok(v),
+            None => Err("error parsing center point".to_string())
+        }
+    )]
+    pub center: Option<(f64, f64)>,
+
+    /// Width of the region shown by the first frame; each algorithm has a default
+    #[arg(long, value_parser = parse_zoom_width)]
+    pub start_zoom: Option<f64>,
+
+    /// Width of the region shown by the last frame
+    #[arg(long, value_parser = parse_zoom_width)]
+    pub end_zoom: f64,
+
+    /// Number of frames
+    #[arg(short = 'n', long, default_value = "100")]
+    pub frames: usize,
+
+    /// Resolution of each frame
+    #[arg(
+        short,
+        long,
+        default_value = "640x480",
+        value_parser = |arg: &str| match parse_pair::<usize>(arg, 'x') {
+            Some(v) => Ok(v),
+            None => Err("error parsing image dimensions".to_string())
+        }
+    )]
+    pub dimensions: (usize, usize),
+
+    #[arg(short, long, default_value = "1000")]
+    pub limit: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value = "escape_time",
+        value_parser = PossibleValuesParser::new(ALGORITHMS)
+    )]
+    pub algorithm: String,
+
+    #[command(flatten)]
+    pub escape: EscapeArguments,
+
+    #[command(flatten)]
+    pub formula: FormulaArguments,
+
+    #[arg(
+        long,
+        default_value = "iterations",
+        value_parser = PossibleValuesParser::new(["iterations", "smooth"])
+    )]
+    pub coloring: String,
+
+    #[arg(short, long)]
+    pub invert: bool,
+
+    /// Prefix of the output files: frames are written to PREFIX_0000.png and on, and the strip
+    /// to PREFIX_strip.png
+    #[arg(short, long, default_value = "zoom")]
+    pub output: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_widths() {
+        let parse = |flags: &[&str]| {
+            Arguments::try_parse_from(["mandelbrot", "zoom"].iter().chain(flags).copied())
+        };
+        assert!(parse(&["--end-zoom", "1e-6"]).is_ok());
+        assert!(parse(&["--end-zoom", "1e-6", "--start-zoom", "3"]).is_ok());
+
+        // A width of zero would need an endless strip, and a negative one makes no sense
+        for width in ["0", "-1", "inf", "NaN"] {
+            assert!(parse(&[&format!("--end-zoom={}", width)]).is_err());
+            assert!(parse(&["--end-zoom", "1e-6", &format!("--start-zoom={}", width)]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_render_groups() {
+        // The render arguments, inlined at the top level, settle on what the subcommands do
+        let flags = ["-a", "phoenix", "--bailout", "square", "--phoenix-p", "0.1,-0.2"];
+        let top = ["mandelbrot", "--limit", "10"].iter().chain(&flags).copied();
+        let render = Arguments::try_parse_from(top).unwrap().render.unwrap();
+        let zoom = ["mandelbrot", "zoom", "--end-zoom", "1"].iter().chain(&flags).copied();
+        let Some(Command::Zoom(zoom)) = Arguments::try_parse_from(zoom).unwrap().command else {
+            unreachable!()
+        };
+        assert_eq!(render.escape(), zoom.escape.escape(&zoom.algorithm));
+        assert_eq!(render.parameters(), zoom.formula.parameters());
+    }
+
+    #[test]
+    fn test_parse_fraction() {
+        assert_eq!(parse_fraction("0"), Ok(0.0));
+        assert_eq!(parse_fraction("0.8"), Ok(0.8));
+        assert_eq!(parse_fraction("1"), Ok(1.0));
+        assert!(parse_fraction("1.5").is_err());
+        assert!(parse_fraction("-0.1").is_err());
+        assert!(parse_fraction("NaN").is_err());
+    }
+}
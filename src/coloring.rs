@@ -0,0 +1,501 @@
+use crate::algorithms::OrbitVisitor;
+use crate::rays::{angle_distance, ExternalAngle};
+use crate::traps::OrbitTrap;
+use num::complex::Complex;
+use std::f64::consts::{PI, TAU};
+
+/// How `Canvas` turns the orbit of a point into a gray level
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coloring {
+    /// Brightness proportional to the escape iteration; the interior is black
+    Iterations,
+    /// Like `Iterations`, but interpolated by how far the last point overshot the escape radius
+    Smooth,
+    /// Brightness from the orbit's closest approach to a trap, decaying over `falloff`
+    Trap { trap: OrbitTrap, falloff: f64 },
+    /// Brightness from an average of a statistic over the escaping orbit
+    Average(Statistic),
+    /// `Iterations` (or `Smooth`) modulated by the argument of the point that escaped
+    Decomposition { decomposition: Decomposition, smooth: bool },
+    /// `Smooth`, crossed by `density` field lines per turn and an equipotential per iteration,
+    /// with the external rays at `angles` picked out in white
+    FieldLines { density: f64, angles: Vec<ExternalAngle> },
+}
+
+pub fn get_coloring(name: &str, stripe_density: f64) -> Coloring {
+    match name {
+        "smooth" => Coloring::Smooth,
+        "triangle" => Coloring::Average(Statistic::TriangleInequality),
+        "stripe" => Coloring::Average(Statistic::Stripe { density: stripe_density }),
+        "curvature" => Coloring::Average(Statistic::Curvature),
+        _ => Coloring::Iterations,
+    }
+}
+
+/// Gathers statistics from every point of an orbit and turns them into a shade
+pub trait Accumulator: OrbitVisitor {
+    /// Shade the orbit between 0 and 1, given the iteration it escaped on (if any) and the
+    /// escape radius it was tested against. `None` leaves the pixel black.
+    fn shade(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64>;
+}
+
+/// How far past the escape radius the final point of an orbit landed, as a fraction of an
+/// iteration: 0 when `z` only just crossed `escape_radius`, 1 when it reached its square.
+/// Orbits that stopped inside the unit circle, by converging rather than escaping, count as 0.
+pub fn escape_fraction(z: Complex<f64>, escape_radius: f64) -> f64 {
+    let fraction = (z.norm().ln() / escape_radius.ln()).log2();
+    if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    }
+}
+
+/// Counts iterations, tracking the last point for the continuous escape time
+pub struct SmoothIterations {
+    limit: usize,
+    last: Complex<f64>,
+}
+
+impl SmoothIterations {
+    pub fn new(limit: usize) -> Self {
+        Self { limit, last: Complex::new(0.0, 0.0) }
+    }
+
+    /// The continuous escape time of the orbit, between `escape - 1` and `escape`
+    pub fn count(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64> {
+        Some(escape? as f64 - escape_fraction(self.last, escape_radius))
+    }
+}
+
+impl OrbitVisitor for SmoothIterations {
+    fn visit(&mut self, z: Complex<f64>) {
+        self.last = z;
+    }
+}
+
+impl Accumulator for SmoothIterations {
+    fn shade(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64> {
+        let count = self.count(escape, escape_radius)?;
+        Some((count / self.limit as f64).clamp(0.0, 1.0))
+    }
+}
+
+/// Per-iteration values averaged by the `OrbitAverage` colorings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    /// Where `|z|` falls between the bounds the triangle inequality puts on `|z² + c|`
+    TriangleInequality,
+    /// `sin(density * arg z)`, scaled into `[0, 1]`
+    Stripe { density: f64 },
+    /// The turning angle of the orbit at each point, relative to a half turn
+    Curvature,
+}
+
+/// Averages a `Statistic` over an orbit.
+///
+/// The shade blends the averages with and without the final point by the escape fraction, so
+/// bands between iteration counts disappear.
+pub struct OrbitAverage {
+    statistic: Statistic,
+    c: Complex<f64>,
+    /// The two most recent points, newest first
+    previous: [Complex<f64>; 2],
+    iterations: usize,
+    sum: f64,
+    count: usize,
+    last_term: Option<f64>,
+}
+
+impl OrbitAverage {
+    pub fn new(statistic: Statistic, c: Complex<f64>) -> Self {
+        Self {
+            statistic,
+            c,
+            previous: [Complex::new(0.0, 0.0); 2],
+            iterations: 0,
+            sum: 0.0,
+            count: 0,
+            last_term: None,
+        }
+    }
+
+    /// The statistic at `z`, or `None` where it is undefined
+    fn term(&self, z: Complex<f64>) -> Option<f64> {
+        let [previous, before] = self.previous;
+
+        match self.statistic {
+            Statistic::TriangleInequality => {
+                let power = previous.norm_sqr();
+                let low = (power - self.c.norm()).abs();
+                let high = power + self.c.norm();
+                if high > low {
+                    Some((z.norm() - low) / (high - low))
+                } else {
+                    None
+                }
+            }
+            Statistic::Stripe { density } => Some(0.5 * (density * z.arg()).sin() + 0.5),
+            Statistic::Curvature => {
+                let turn = previous - before;
+                if self.iterations < 2 || turn.norm_sqr() == 0.0 {
+                    None
+                } else {
+                    Some(((z - previous) / turn).arg().abs() / PI)
+                }
+            }
+        }
+    }
+}
+
+impl OrbitVisitor for OrbitAverage {
+    fn visit(&mut self, z: Complex<f64>) {
+        self.last_term = self.term(z);
+        if let Some(term) = self.last_term {
+            self.sum += term;
+            self.count += 1;
+        }
+        self.previous = [z, self.previous[0]];
+        self.iterations += 1;
+    }
+}
+
+impl Accumulator for OrbitAverage {
+    fn shade(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64> {
+        escape?;
+        if self.count == 0 {
+            return Some(0.0);
+        }
+
+        let average = self.sum / self.count as f64;
+        let previous_average = match self.last_term {
+            Some(term) if self.count > 1 => (self.sum - term) / (self.count - 1) as f64,
+            _ => average,
+        };
+        let fraction = escape_fraction(self.previous[0], escape_radius);
+
+        Some(((1.0 - fraction) * average + fraction * previous_average).clamp(0.0, 1.0))
+    }
+}
+
+/// Ways of splitting the bands between iteration counts by the argument of the escaped point
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decomposition {
+    /// Keep the half of each band where `Im z` is non-negative, blacking out the other
+    Binary,
+    /// Darken each band continuously with the argument of `z`, from 0 to a full turn
+    Angular,
+    /// Tile a texture over the exterior: across by the argument of `z`, down by the fraction of
+    /// an iteration it escaped with
+    Texture(Texture),
+}
+
+/// Shades the escape time of an orbit by the `Decomposition` of its final point
+pub struct Decomposed<'a> {
+    decomposition: &'a Decomposition,
+    smooth: SmoothIterations,
+    continuous: bool,
+}
+
+impl<'a> Decomposed<'a> {
+    /// Decompose iteration bands up to `limit`, which are smoothed if `continuous` is set
+    pub fn new(decomposition: &'a Decomposition, limit: usize, continuous: bool) -> Self {
+        Self { decomposition, smooth: SmoothIterations::new(limit), continuous }
+    }
+}
+
+impl OrbitVisitor for Decomposed<'_> {
+    fn visit(&mut self, z: Complex<f64>) {
+        self.smooth.visit(z);
+    }
+}
+
+impl Accumulator for Decomposed<'_> {
+    fn shade(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64> {
+        let count = match self.continuous {
+            true => self.smooth.count(escape, escape_radius)?,
+            false => escape? as f64,
+        };
+        let z = self.smooth.last;
+        let angle = z.arg().rem_euclid(TAU) / TAU;
+
+        let factor = match self.decomposition {
+            Decomposition::Binary if z.im >= 0.0 => 1.0,
+            Decomposition::Binary => 0.0,
+            Decomposition::Angular => angle,
+            Decomposition::Texture(texture) => {
+                let v = escape_fraction(z, escape_radius);
+                texture.sample(angle, v) as f64 / u8::MAX as f64
+            }
+        };
+
+        Some((count / self.smooth.limit as f64 * factor).clamp(0.0, 1.0))
+    }
+}
+
+/// Width of field lines and equipotentials, as a fraction of the spacing between them
+const FIELD_LINE_WIDTH: f64 = 0.08;
+
+/// How far, in turns, the argument of the escaped point may be from a highlighted ray's
+const RAY_HIGHLIGHT_WIDTH: f64 = 0.01;
+
+/// Brightness left on field lines and equipotentials
+const FIELD_LINE_SHADE: f64 = 0.35;
+
+/// Follows one highlighted ray along an orbit
+struct RayTrack {
+    /// The ray's angle doubled once per point visited so far
+    angle: ExternalAngle,
+    /// Distance in turns between the last point's argument and the ray's angle at that point
+    distance: f64,
+    /// Set once a point is nearer the other half of a turn than the ray's angle, so the orbit
+    /// follows a different preimage of it
+    lost: bool,
+}
+
+/// Shades the exterior by its potential and external angle.
+///
+/// The argument of `z` after `n` iterations is the external angle of `c` doubled `n - 1`
+/// times, so lines evenly spaced in it are field lines. The argument of the escaped point alone
+/// matches `2^(n - 1)` rays, so a highlighted ray is followed through the whole orbit, each
+/// point picking out one half of the candidates left by the point after it.
+pub struct FieldLines {
+    density: f64,
+    rays: Vec<RayTrack>,
+    smooth: SmoothIterations,
+}
+
+impl FieldLines {
+    pub fn new(density: f64, angles: &[ExternalAngle], limit: usize) -> Self {
+        let rays = angles
+            .iter()
+            .map(|&angle| RayTrack { angle, distance: 0.5, lost: false })
+            .collect();
+        Self { density, rays, smooth: SmoothIterations::new(limit) }
+    }
+}
+
+impl OrbitVisitor for FieldLines {
+    fn visit(&mut self, z: Complex<f64>) {
+        self.smooth.visit(z);
+
+        let angle = z.arg().rem_euclid(TAU) / TAU;
+        for ray in &mut self.rays {
+            ray.distance = angle_distance(angle, ray.angle.turns());
+            if ray.distance >= 0.25 {
+                ray.lost = true;
+            }
+            ray.angle = ray.angle.doubled(1);
+        }
+    }
+}
+
+impl Accumulator for FieldLines {
+    fn shade(&self, escape: Option<usize>, escape_radius: f64) -> Option<f64> {
+        let base = self.smooth.shade(escape, escape_radius)?;
+        let z = self.smooth.last;
+
+        if self.rays.iter().any(|ray| !ray.lost && ray.distance < RAY_HIGHLIGHT_WIDTH) {
+            return Some(1.0);
+        }
+
+        let angle = z.arg().rem_euclid(TAU) / TAU;
+        let line = angle_distance(angle * self.density, 0.0) < FIELD_LINE_WIDTH / 2.0;
+        let potential = angle_distance(escape_fraction(z, escape_radius), 0.0);
+        if line || potential < FIELD_LINE_WIDTH / 2.0 {
+            Some(base * FIELD_LINE_SHADE)
+        } else {
+            Some(base)
+        }
+    }
+}
+
+/// A grayscale image that can be sampled with texture coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, texels: Vec<u8>) -> Self {
+        assert_eq!(texels.len(), width * height);
+        Self { width, height, texels }
+    }
+
+    /// Load an image file, converted to grayscale
+    pub fn open(path: &str) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|err| format!("error opening texture {}: {}", path, err))?
+            .to_luma();
+        let (width, height) = image.dimensions();
+        Ok(Self::new(width as usize, height as usize, image.into_raw()))
+    }
+
+    /// Sample the nearest texel to `(u, v)`, each wrapped into `[0, 1)`
+    pub fn sample(&self, u: f64, v: f64) -> u8 {
+        let x = (u.rem_euclid(1.0) * self.width as f64) as usize;
+        let y = (v.rem_euclid(1.0) * self.height as f64) as usize;
+        self.texels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_texture_sample() {
+        let texture = Texture::new(2, 2, vec![0, 64, 128, 255]);
+        assert_eq!(texture.sample(0.0, 0.0), 0);
+        assert_eq!(texture.sample(0.75, 0.25), 64);
+        assert_eq!(texture.sample(0.25, 0.75), 128);
+        assert_eq!(texture.sample(0.99, 0.99), 255);
+
+        // Coordinates wrap around
+        assert_eq!(texture.sample(1.75, -0.75), 64);
+    }
+
+    #[test]
+    fn test_escape_fraction() {
+        assert_eq!(escape_fraction(Complex::new(4.0, 0.0), 4.0), 0.0);
+        assert_eq!(escape_fraction(Complex::new(0.0, 16.0), 4.0), 1.0);
+        assert!((escape_fraction(Complex::new(8.0, 0.0), 4.0) - 1.5f64.log2()).abs() < 1e-12);
+        // Orbits that converged instead of escaping
+        assert_eq!(escape_fraction(Complex::new(0.999, 0.0), 4.0), 0.0);
+        assert_eq!(escape_fraction(Complex::new(1.0, 0.0), 4.0), 0.0);
+    }
+
+    #[test]
+    fn test_smooth_iterations() {
+        let mut smooth = SmoothIterations::new(10);
+        smooth.visit(Complex::new(1.0, 0.0));
+        smooth.visit(Complex::new(16.0, 0.0));
+        assert_eq!(smooth.shade(Some(2), 4.0), Some(0.1));
+        assert_eq!(smooth.shade(None, 4.0), None);
+    }
+
+    #[test]
+    fn test_binary_decomposition() {
+        let decomposition = Decomposition::Binary;
+
+        let mut upper = Decomposed::new(&decomposition, 10, false);
+        upper.visit(Complex::new(-3.0, 3.0));
+        assert_eq!(upper.shade(Some(5), 4.0), Some(0.5));
+        assert_eq!(upper.shade(None, 4.0), None);
+
+        let mut lower = Decomposed::new(&decomposition, 10, false);
+        lower.visit(Complex::new(-3.0, -3.0));
+        assert_eq!(lower.shade(Some(5), 4.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_angular_decomposition() {
+        let decomposition = Decomposition::Angular;
+
+        // A quarter turn past the escape radius, escaping with a whole iteration to spare
+        let mut angular = Decomposed::new(&decomposition, 10, true);
+        angular.visit(Complex::new(0.0, 16.0));
+        assert_eq!(angular.shade(Some(5), 4.0), Some(0.1));
+
+        // Three quarters of a turn
+        let mut angular = Decomposed::new(&decomposition, 10, false);
+        angular.visit(Complex::new(0.0, -16.0));
+        assert!((angular.shade(Some(4), 4.0).unwrap() - 0.3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_texture_decomposition() {
+        let decomposition = Decomposition::Texture(Texture::new(2, 2, vec![0, 64, 128, 255]));
+
+        // Early in the turn, and overshooting by three quarters of an iteration
+        let mut textured = Decomposed::new(&decomposition, 10, false);
+        textured.visit(Complex::from_polar(10.31, TAU * 0.3));
+        let shade = textured.shade(Some(10), 4.0).unwrap();
+        assert!((shade - 128.0 / 255.0).abs() < 1e-12);
+
+        // Past half a turn, and only just escaped
+        let mut textured = Decomposed::new(&decomposition, 10, false);
+        textured.visit(Complex::from_polar(4.0, TAU * 0.6));
+        let shade = textured.shade(Some(10), 4.0).unwrap();
+        assert!((shade - 64.0 / 255.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_field_lines() {
+        let angles = [ExternalAngle::new(1, 3).unwrap()];
+
+        // After three iterations the ray at 1/3 has doubled twice, to 1/3 again
+        let mut field = FieldLines::new(8.0, &angles, 10);
+        field.visit(Complex::from_polar(0.5, TAU * 0.4));
+        field.visit(Complex::from_polar(2.0, TAU * 0.6));
+        field.visit(Complex::from_polar(8.0, TAU / 3.0));
+        assert_eq!(field.shade(Some(3), 4.0), Some(1.0));
+        assert_eq!(field.shade(None, 4.0), None);
+
+        // Arriving at the same angle from the other preimage of 1/3 is a different ray
+        let mut field = FieldLines::new(8.0, &angles, 10);
+        field.visit(Complex::from_polar(3.0, TAU * (1.0 / 3.0 + 0.5)));
+        field.visit(Complex::from_polar(8.0, TAU * 2.0 / 3.0));
+        assert_ne!(field.shade(Some(2), 4.0), Some(1.0));
+
+        // A sixteenth of a turn is between field lines, but one falls on a quarter turn
+        let mut field = FieldLines::new(8.0, &angles, 10);
+        field.visit(Complex::from_polar(8.0, TAU / 16.0));
+        let base = field.smooth.shade(Some(5), 4.0).unwrap();
+        assert_eq!(field.shade(Some(5), 4.0), Some(base));
+
+        let mut field = FieldLines::new(8.0, &angles, 10);
+        field.visit(Complex::from_polar(8.0, TAU / 4.0));
+        assert_eq!(field.shade(Some(5), 4.0), Some(base * FIELD_LINE_SHADE));
+
+        // Only just escaping lies on an equipotential
+        let mut field = FieldLines::new(8.0, &angles, 10);
+        field.visit(Complex::from_polar(4.0, TAU / 16.0));
+        assert_eq!(field.shade(Some(5), 4.0), Some(0.5 * FIELD_LINE_SHADE));
+    }
+
+    #[test]
+    fn test_stripe_average() {
+        let stripes = Statistic::Stripe { density: 1.0 };
+        let mut average = OrbitAverage::new(stripes, Complex::new(0.0, 0.0));
+        average.visit(Complex::new(0.0, 1.0));
+        average.visit(Complex::new(-1.0, 0.0));
+        average.visit(Complex::new(4.0, 0.0));
+
+        // Terms are 1.0, 0.5 and 0.5: with no overshoot only the full average counts
+        assert!((average.shade(Some(3), 4.0).unwrap() - 2.0 / 3.0).abs() < 1e-12);
+        // Overshooting by a full iteration drops the final point
+        average.visit(Complex::new(0.0, 16.0));
+        assert!((average.shade(Some(4), 4.0).unwrap() - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(average.shade(None, 4.0), None);
+    }
+
+    #[test]
+    fn test_triangle_inequality_average() {
+        let c = Complex::new(0.5, 0.0);
+        let mut average = OrbitAverage::new(Statistic::TriangleInequality, c);
+
+        // The first point has no bound to compare against
+        average.visit(c);
+        assert_eq!(average.count, 0);
+
+        // z² + c with both terms pointing the same way sits on the upper bound
+        average.visit(c * c + c);
+        assert_eq!(average.last_term, Some(1.0));
+    }
+
+    #[test]
+    fn test_curvature_average() {
+        let mut average = OrbitAverage::new(Statistic::Curvature, Complex::new(0.0, 0.0));
+        for z in [Complex::new(1.0, 0.0), Complex::new(2.0, 0.0), Complex::new(3.0, 0.0)] {
+            average.visit(z);
+        }
+        assert_eq!(average.last_term, Some(0.0));
+
+        // Doubling back is a half turn
+        average.visit(Complex::new(2.0, 0.0));
+        assert_eq!(average.last_term, Some(1.0));
+    }
+}
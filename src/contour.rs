@@ -0,0 +1,407 @@
+use crate::mandelbrot::ComplexRegion;
+use crate::types::Dimensions;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// A closed loop of positions in pixel space, the last joined back to the first
+pub type Contour = Vec<(f64, f64)>;
+
+/// How contours are drawn into an SVG
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// Each band between consecutive levels as a filled polygon, shaded lighter as it rises
+    Bands,
+    /// Each level as an outline
+    Isolines,
+}
+
+/// The edges of bands `width` counts wide over the escape counts `values`, each halfway between
+/// the counts either side of it, up to `limit`. A level with no counts between it and the one
+/// before would trace the same loops again, so those are left out, and the levels stop at the
+/// first one above every count, which outlines the set.
+pub fn band_levels(values: &[Option<f64>], width: usize, limit: usize) -> Vec<f64> {
+    let mut counts: Vec<f64> = values.iter().flatten().copied().collect();
+    counts.sort_by(f64::total_cmp);
+
+    let mut levels = Vec::new();
+    let mut below = 0;
+    for count in (width.max(1)..=limit).step_by(width.max(1)) {
+        let level = count as f64 - 0.5;
+        let under = counts.partition_point(|&count| count < level);
+        if levels.is_empty() || under > below {
+            levels.push(level);
+            below = under;
+        }
+        if under == counts.len() {
+            break;
+        }
+    }
+    levels
+}
+
+/// Trace the contours of `values` at each of `levels` by marching squares, returning the loops
+/// of each level in the same order. The loops wind so that values at or above the level lie on
+/// their left, as seen on screen.
+///
+/// Value `i` is placed at the pixel space position `(i % width, i / width)`, which is where
+/// `ComplexRegion::point_to_position` puts the point the renderer sampled for it. Pixels inside
+/// the set (`None`) count as above every level, and everything beyond the image as below, so
+/// every loop is closed.
+pub fn trace(values: &[Option<f64>], bounds: Dimensions, levels: &[f64]) -> Vec<Vec<Contour>> {
+    assert_eq!(values.len(), bounds.total_pixels());
+    let (width, height) = (bounds.width as i64, bounds.height as i64);
+
+    let sample = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            f64::NEG_INFINITY
+        } else {
+            values[(y * width + x) as usize].unwrap_or(f64::INFINITY)
+        }
+    };
+
+    let mut sorted: Vec<usize> = (0..levels.len()).collect();
+    sorted.sort_by(|&a, &b| levels[a].total_cmp(&levels[b]));
+
+    // The segments of each level, keyed by the edge each starts on
+    let mut segments: Vec<HashMap<Edge, Edge>> = vec![HashMap::new(); levels.len()];
+    let mut crossings: Vec<(usize, Edge)> = Vec::with_capacity(4);
+    for y in -1..height {
+        for x in -1..width {
+            // The corners clockwise on screen, from the upper left, and the sides leaving them
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let values = corners.map(|(x, y)| sample(x, y));
+            let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+            // Only the levels in between the corners cross the cell
+            let first = sorted.partition_point(|&level| levels[level] <= low);
+            for &level in sorted[first..].iter().take_while(|&&level| levels[level] <= high) {
+                let above = values.map(|v| v >= levels[level]);
+
+                crossings.clear();
+                for side in 0..4 {
+                    let next = (side + 1) % 4;
+                    if above[side] != above[next] {
+                        crossings.push((side, Edge::between(corners[side], corners[next])));
+                    }
+                }
+
+                // Segments run from a side that goes from below to above the level, clockwise,
+                // to the neighboring crossing that goes back below. At a saddle, where there are
+                // two of each, the value at the center decides which corners are joined.
+                let count = crossings.len();
+                let center_above = values.iter().sum::<f64>() / 4.0 >= levels[level];
+                for (i, &(side, edge)) in crossings.iter().enumerate() {
+                    if above[side] {
+                        continue;
+                    }
+                    let end = match count == 4 && center_above {
+                        true => crossings[(i + count - 1) % count].1,
+                        false => crossings[(i + 1) % count].1,
+                    };
+                    segments[level].insert(edge, end);
+                }
+            }
+        }
+    }
+
+    segments
+        .into_iter()
+        .zip(levels)
+        .map(|(segments, &level)| link(segments, &sample, level))
+        .collect()
+}
+
+/// An edge between two neighboring samples, named by the first of them (leftmost or topmost)
+/// and whether it runs down rather than across
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Edge {
+    x: i64,
+    y: i64,
+    vertical: bool,
+}
+
+impl Edge {
+    fn between(a: (i64, i64), b: (i64, i64)) -> Self {
+        Self { x: a.0.min(b.0), y: a.1.min(b.1), vertical: a.0 == b.0 }
+    }
+
+    /// Where `level` crosses this edge. Next to the set the contour is taken halfway between
+    /// the samples, and next to the outside of the image it runs through the edge pixels.
+    fn crossing(&self, sample: &impl Fn(i64, i64) -> f64, level: f64) -> (f64, f64) {
+        let (dx, dy) = if self.vertical { (0, 1) } else { (1, 0) };
+        let (a, b) = (sample(self.x, self.y), sample(self.x + dx, self.y + dy));
+        let t = if a == f64::NEG_INFINITY {
+            1.0
+        } else if b == f64::NEG_INFINITY {
+            0.0
+        } else if a.is_infinite() || b.is_infinite() {
+            0.5
+        } else {
+            (level - a) / (b - a)
+        };
+        (self.x as f64 + t * dx as f64, self.y as f64 + t * dy as f64)
+    }
+}
+
+/// Chain the segments of one level into loops
+fn link(
+    mut segments: HashMap<Edge, Edge>, sample: &impl Fn(i64, i64) -> f64, level: f64,
+) -> Vec<Contour> {
+    let mut contours = Vec::new();
+    while let Some(&start) = segments.keys().next() {
+        let mut contour = Vec::new();
+        let mut edge = start;
+        while let Some(next) = segments.remove(&edge) {
+            contour.push(edge.crossing(sample, level));
+            edge = next;
+        }
+        contours.push(contour);
+    }
+    contours
+}
+
+/// The area enclosed by `contour`, in square pixels
+pub fn area(contour: &Contour) -> f64 {
+    let twice: f64 = (0..contour.len())
+        .map(|i| {
+            let ((x0, y0), (x1, y1)) = (contour[i], contour[(i + 1) % contour.len()]);
+            x0 * y1 - x1 * y0
+        })
+        .sum();
+    twice.abs() / 2.0
+}
+
+/// Drop the points of `contour` that stray less than `tolerance` pixels from the line through
+/// their neighbors, by the Douglas-Peucker algorithm
+pub fn simplify(contour: &Contour, tolerance: f64) -> Contour {
+    if contour.len() < 4 || tolerance <= 0.0 {
+        return contour.clone();
+    }
+
+    // Split the loop in two at the point furthest from the first, and simplify each half
+    let first = contour[0];
+    let furthest = (1..contour.len())
+        .max_by(|&a, &b| distance(contour[a], first).total_cmp(&distance(contour[b], first)))
+        .unwrap();
+    let mut closed = contour.clone();
+    closed.push(first);
+
+    let mut keep = vec![false; closed.len()];
+    (keep[0], keep[furthest]) = (true, true);
+    mark(&closed, 0, furthest, tolerance, &mut keep);
+    mark(&closed, furthest, contour.len(), tolerance, &mut keep);
+
+    contour.iter().zip(keep).filter(|(_, keep)| *keep).map(|(&point, _)| point).collect()
+}
+
+/// Keep the point between `first` and `last` furthest from the line through them, if it is
+/// further than `tolerance`, and carry on either side of it
+fn mark(points: &[(f64, f64)], first: usize, last: usize, tolerance: f64, keep: &mut [bool]) {
+    let furthest = (first + 1..last)
+        .map(|i| (i, line_distance(points[i], points[first], points[last])))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((i, offset)) = furthest {
+        if offset > tolerance {
+            keep[i] = true;
+            mark(points, first, i, tolerance, keep);
+            mark(points, i, last, tolerance, keep);
+        }
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// The distance from `point` to the line through `a` and `b`
+fn line_distance(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let length = distance(a, b);
+    if length == 0.0 {
+        return distance(point, a);
+    }
+    ((b.0 - a.0) * (a.1 - point.1) - (a.0 - point.0) * (b.1 - a.1)).abs() / length
+}
+
+/// Simplify each of `contours` and drop those left enclosing less than `min_area` square
+/// pixels, which would only be specks when cut or plotted
+pub fn clean(contours: Vec<Contour>, tolerance: f64, min_area: f64) -> Vec<Contour> {
+    contours
+        .iter()
+        .map(|contour| simplify(contour, tolerance))
+        .filter(|contour| contour.len() >= 3 && area(contour) >= min_area)
+        .collect()
+}
+
+/// Write the contours traced at each of a rising series of levels as an SVG the size of the
+/// image, noting the region of the complex plane it shows
+pub fn write_svg<W: Write>(
+    out: &mut W, bounds: Dimensions, region: ComplexRegion, levels: &[Vec<Contour>], style: Style,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" "#,
+            r#"viewBox="0 0 {0} {1}">"#
+        ),
+        bounds.width, bounds.height
+    )?;
+    writeln!(
+        out,
+        "<desc>upper left {}, lower right {}</desc>",
+        region.upper_left, region.lower_right
+    )?;
+
+    for (i, contours) in levels.iter().enumerate() {
+        match style {
+            // A band runs from its level up to the next, so its polygon is the loops of both,
+            // with the even-odd rule cutting the upper ones out
+            Style::Bands => {
+                let upper = levels.get(i + 1).map(Vec::as_slice).unwrap_or_default();
+                let gray = (u8::MAX as usize * (i + 1) / levels.len()) as u8;
+                writeln!(
+                    out,
+                    concat!(
+                        r#"<path fill="rgb({0},{0},{0})" fill-rule="evenodd" "#,
+                        r#"stroke="black" stroke-width="0.25" d="{1}"/>"#
+                    ),
+                    gray,
+                    path(contours.iter().chain(upper))
+                )?;
+            }
+            Style::Isolines => writeln!(
+                out,
+                r#"<path fill="none" stroke="black" stroke-width="0.5" d="{}"/>"#,
+                path(contours.iter())
+            )?,
+        }
+    }
+
+    writeln!(out, "</svg>")
+}
+
+/// The SVG path data drawing each of `contours` as a closed shape
+fn path<'a>(contours: impl Iterator<Item = &'a Contour>) -> String {
+    let mut data = Vec::new();
+    for contour in contours {
+        for (i, (x, y)) in contour.iter().enumerate() {
+            data.push(format!("{}{:.2} {:.2}", if i == 0 { "M" } else { "L" }, x, y));
+        }
+        data.push("Z".to_string());
+    }
+    data.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot::calculate_region;
+    use num::Complex;
+
+    /// Values rising towards the center of the image, like escape times around the set, with
+    /// the middle pixel inside it
+    fn cone(bounds: Dimensions) -> Vec<Option<f64>> {
+        let center = ((bounds.width / 2) as f64, (bounds.height / 2) as f64);
+        (0..bounds.total_pixels())
+            .map(|i| {
+                let radius =
+                    distance(((i % bounds.width) as f64, (i / bounds.width) as f64), center);
+                (radius > 0.0).then_some(-radius)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_circle() {
+        let bounds = Dimensions::new(41, 41);
+        let contours = trace(&cone(bounds), bounds, &[-10.0, -30.0]);
+
+        // The level 10 pixels from the center is a circle, and positions are those of the region
+        assert_eq!(contours[0].len(), 1);
+        let circle = &contours[0][0];
+        assert!((area(circle) - std::f64::consts::PI * 100.0).abs() < 2.0);
+        let region = calculate_region(41.0, (0.0, 0.0));
+        let point = region.pixel_to_point(bounds, (30, 20).into());
+        assert_eq!(region.point_to_position(bounds, point), (30.0, 20.0));
+        assert!(circle.iter().any(|&position| distance(position, (30.0, 20.0)) < 1e-9));
+
+        // Above the level on the left, as seen on screen (y down): counterclockwise on screen
+        // gives negative area by the shoelace formula
+        let signed: f64 = (0..circle.len())
+            .map(|i| {
+                let ((x0, y0), (x1, y1)) = (circle[i], circle[(i + 1) % circle.len()]);
+                x0 * y1 - x1 * y0
+            })
+            .sum();
+        assert!(signed < 0.0);
+
+        // The level 30 pixels out is cut off by the edges of the image, and runs along them
+        assert_eq!(contours[1].len(), 1);
+        assert!((area(&contours[1][0]) - 40.0 * 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_trace_saddle() {
+        // A checkerboard of two high pixels; the low center keeps them apart
+        let bounds = Dimensions::new(2, 2);
+        let values = [Some(1.0), Some(0.0), Some(0.0), Some(1.0)];
+        assert_eq!(trace(&values, bounds, &[0.6])[0].len(), 2);
+        // A set pixel raises the center, joining them
+        let values = [Some(1.0), Some(0.0), Some(0.0), None];
+        assert_eq!(trace(&values, bounds, &[0.6])[0].len(), 1);
+    }
+
+    #[test]
+    fn test_clean() {
+        // A square with extra points along its sides, and a speck
+        let square =
+            vec![(0.0, 0.0), (5.0, 0.1), (10.0, 0.0), (10.0, 10.0), (5.0, 9.9), (0.0, 10.0)];
+        let speck = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        assert_eq!(simplify(&square, 0.5).len(), 4);
+        assert_eq!(simplify(&square, 0.01), square);
+
+        let contours = clean(vec![square, speck], 0.5, 4.0);
+        assert_eq!(contours, vec![vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]]);
+    }
+
+    #[test]
+    fn test_band_levels() {
+        // Counts rising towards the set in the middle, with a gap between 4 and 50, rendered
+        // with a far larger limit
+        let bounds = Dimensions::new(21, 21);
+        let count = |v: f64| match (v + 10.0).max(0.0).floor() {
+            count if count > 4.0 => count * 10.0,
+            count => count,
+        };
+        let values: Vec<_> = cone(bounds).iter().map(|v| v.map(count)).collect();
+        let levels = band_levels(&values, 1, 500);
+        assert_eq!(levels, vec![0.5, 1.5, 2.5, 3.5, 4.5, 50.5, 60.5, 70.5, 80.5, 90.5]);
+        assert_eq!(band_levels(&values, 20, 500), vec![19.5, 59.5, 79.5, 99.5]);
+
+        // One path per band and one for the outline of the set, rather than one per count
+        let contours = trace(&values, bounds, &levels);
+        let region = ComplexRegion::new(Complex::new(-1.0, 1.0), Complex::new(1.0, -1.0));
+        let mut svg = Vec::new();
+        write_svg(&mut svg, bounds, region, &contours, Style::Bands).unwrap();
+        assert_eq!(String::from_utf8(svg).unwrap().matches("<path").count(), 10);
+    }
+
+    #[test]
+    fn test_write_svg() {
+        let bounds = Dimensions::new(21, 21);
+        let contours = trace(&cone(bounds), bounds, &[-8.0, -4.0]);
+        let region = ComplexRegion::new(Complex::new(-1.0, 1.0), Complex::new(1.0, -1.0));
+
+        for (style, fill) in
+            [(Style::Bands, "fill=\"rgb(127,127,127)\""), (Style::Isolines, "none")]
+        {
+            let mut svg = Vec::new();
+            write_svg(&mut svg, bounds, region, &contours, style).unwrap();
+            let svg = String::from_utf8(svg).unwrap();
+            assert!(svg.starts_with("<svg"));
+            assert_eq!(svg.matches("<path").count(), 2);
+            assert_eq!(svg.matches('Z').count(), if style == Style::Bands { 3 } else { 2 });
+            assert!(svg.contains(fill));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+/// The cumulative distribution of escape iterations over a frame.
+///
+/// Mapping iteration counts through it spreads the gray levels evenly over the pixels of the
+/// frame, instead of evenly over the range of counts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// `cdf[n]` is the fraction of counted pixels that escaped within `n` iterations
+    cdf: Vec<f64>,
+}
+
+impl Histogram {
+    /// Build the histogram of `values`, as produced by `Canvas::render_iterations`.
+    ///
+    /// Interior points are counted in the final bin unless `exclude_interior` is set, in which
+    /// case the exterior gets the full range of gray levels to itself.
+    pub fn new(values: &[Option<f64>], limit: usize, exclude_interior: bool) -> Self {
+        let mut counts = vec![0usize; limit + 1];
+        for value in values {
+            match value {
+                Some(count) => counts[(count.max(0.0) as usize).min(limit)] += 1,
+                None if !exclude_interior => counts[limit] += 1,
+                None => {}
+            }
+        }
+
+        let total = counts.iter().sum::<usize>().max(1) as f64;
+        let cdf = counts
+            .iter()
+            .scan(0, |sum, count| {
+                *sum += count;
+                Some(*sum as f64 / total)
+            })
+            .collect();
+
+        Self { cdf }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    /// Look up an iteration count in the distribution, interpolating between bins for
+    /// continuous counts
+    pub fn equalize(&self, count: f64) -> f64 {
+        let count = count.clamp(0.0, self.limit() as f64);
+        let bin = count as usize;
+        let next = (bin + 1).min(self.limit());
+        let fraction = count - bin as f64;
+
+        self.cdf[bin] * (1.0 - fraction) + self.cdf[next] * fraction
+    }
+
+    /// Blend the equalized shade of `count` with the linear one, where `linear` is the weight
+    /// of the linear mapping
+    pub fn shade(&self, count: f64, linear: f64) -> f64 {
+        let linear_shade = count / self.limit() as f64;
+        (self.equalize(count) * (1.0 - linear) + linear_shade * linear).clamp(0.0, 1.0)
+    }
+
+    /// Carry over `momentum` of a previous frame's distribution, so consecutive frames of an
+    /// animation don't flicker as their histograms change.
+    ///
+    /// Distributions with a different limit can't be compared and leave this one untouched.
+    pub fn stabilize(&mut self, previous: &Histogram, momentum: f64) {
+        if previous.cdf.len() != self.cdf.len() {
+            return;
+        }
+        for (value, previous) in self.cdf.iter_mut().zip(&previous.cdf) {
+            *value = previous * momentum + *value * (1.0 - momentum);
+        }
+    }
+
+    /// Parse a distribution saved by `to_state`
+    pub fn from_state(s: &str) -> Result<Self, String> {
+        let cdf = s
+            .lines()
+            .map(|line| line.trim().parse::<f64>().map_err(|_| format!("invalid value '{}'", line)))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        if cdf.is_empty() {
+            return Err("empty histogram state".to_string());
+        }
+        Ok(Self { cdf })
+    }
+
+    /// Serialize the distribution, one bin per line
+    pub fn to_state(&self) -> String {
+        self.cdf.iter().map(|value| format!("{}\n", value)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cumulative_distribution() {
+        let values = [Some(1.0), Some(1.0), Some(1.0), Some(3.0), None];
+        let histogram = Histogram::new(&values, 4, false);
+        assert_eq!(histogram.cdf, vec![0.0, 0.6, 0.6, 0.8, 1.0]);
+
+        let histogram = Histogram::new(&values, 4, true);
+        assert_eq!(histogram.cdf, vec![0.0, 0.75, 0.75, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_equalize_interpolates() {
+        let histogram = Histogram::new(&[Some(1.0), Some(3.0)], 4, true);
+        assert_eq!(histogram.equalize(1.0), 0.5);
+        assert_eq!(histogram.equalize(2.5), 0.75);
+        assert_eq!(histogram.equalize(10.0), 1.0);
+    }
+
+    #[test]
+    fn test_blend_with_linear() {
+        let histogram = Histogram::new(&[Some(1.0), Some(3.0)], 4, true);
+        assert_eq!(histogram.shade(1.0, 0.0), 0.5);
+        assert_eq!(histogram.shade(1.0, 1.0), 0.25);
+        assert_eq!(histogram.shade(1.0, 0.5), 0.375);
+    }
+
+    #[test]
+    fn test_stabilize() {
+        let mut histogram = Histogram::new(&[Some(0.0)], 1, true);
+        let previous = Histogram::new(&[Some(1.0)], 1, true);
+        histogram.stabilize(&previous, 0.75);
+        assert_eq!(histogram.cdf, vec![0.25, 1.0]);
+
+        // A different limit is ignored
+        let other = Histogram::new(&[Some(1.0)], 2, true);
+        histogram.stabilize(&other, 0.75);
+        assert_eq!(histogram.cdf, vec![0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let histogram = Histogram::new(&[Some(1.0), Some(2.0), None], 3, false);
+        assert_eq!(Histogram::from_state(&histogram.to_state()), Ok(histogram));
+        assert!(Histogram::from_state("").is_err());
+        assert!(Histogram::from_state("0.5\nx").is_err());
+    }
+}
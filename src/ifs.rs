@@ -0,0 +1,488 @@
+use crate::mandelbrot::{calculate_region, ComplexRegion};
+use crate::types::Dimensions;
+use colors_transform::{Color, Hsl};
+use num::complex::Complex;
+use rayon::prelude::*;
+
+/// Number of iterations each chaos game run performs before plotting, so the point has
+/// settled onto the attractor.
+const SETTLE_ITERATIONS: usize = 20;
+
+/// Nonlinear functions applied after a transform's affine map, as described in
+/// "The Fractal Flame Algorithm" (Draves & Reckase).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+    Polar,
+    Handkerchief,
+    Heart,
+    Disc,
+    Spiral,
+    Hyperbolic,
+    Diamond,
+}
+
+impl Variation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Variation::Linear),
+            "sinusoidal" => Some(Variation::Sinusoidal),
+            "spherical" => Some(Variation::Spherical),
+            "swirl" => Some(Variation::Swirl),
+            "horseshoe" => Some(Variation::Horseshoe),
+            "polar" => Some(Variation::Polar),
+            "handkerchief" => Some(Variation::Handkerchief),
+            "heart" => Some(Variation::Heart),
+            "disc" => Some(Variation::Disc),
+            "spiral" => Some(Variation::Spiral),
+            "hyperbolic" => Some(Variation::Hyperbolic),
+            "diamond" => Some(Variation::Diamond),
+            _ => None,
+        }
+    }
+
+    /// Apply the variation to the point `(x, y)`
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let r = r2.sqrt();
+        // The flame paper measures theta from the y axis
+        let theta = x.atan2(y);
+
+        match self {
+            Variation::Linear => (x, y),
+            Variation::Sinusoidal => (x.sin(), y.sin()),
+            Variation::Spherical => {
+                let r2 = r2 + f64::EPSILON;
+                (x / r2, y / r2)
+            }
+            Variation::Swirl => {
+                let (sin, cos) = r2.sin_cos();
+                (x * sin - y * cos, x * cos + y * sin)
+            }
+            Variation::Horseshoe => {
+                let r = r + f64::EPSILON;
+                ((x - y) * (x + y) / r, 2.0 * x * y / r)
+            }
+            Variation::Polar => (theta / std::f64::consts::PI, r - 1.0),
+            Variation::Handkerchief => (r * (theta + r).sin(), r * (theta - r).cos()),
+            Variation::Heart => (r * (theta * r).sin(), -r * (theta * r).cos()),
+            Variation::Disc => {
+                let (sin, cos) = (std::f64::consts::PI * r).sin_cos();
+                let scale = theta / std::f64::consts::PI;
+                (scale * sin, scale * cos)
+            }
+            Variation::Spiral => {
+                let r = r + f64::EPSILON;
+                ((theta.cos() + r.sin()) / r, (theta.sin() - r.cos()) / r)
+            }
+            Variation::Hyperbolic => {
+                let r = r + f64::EPSILON;
+                (theta.sin() / r, r * theta.cos())
+            }
+            Variation::Diamond => (theta.sin() * r.cos(), theta.cos() * r.sin()),
+        }
+    }
+}
+
+/// One map of an iterated function system: an affine map `(a x + b y + e, c x + d y + f)`
+/// followed by a weighted sum of variations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub coefficients: [f64; 6],
+    pub weight: f64,
+    pub color: f64,
+    pub variations: Vec<(Variation, f64)>,
+}
+
+impl Transform {
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.coefficients;
+        let (x, y) = (a * x + b * y + e, c * x + d * y + f);
+
+        self.variations.iter().fold((0.0, 0.0), |(sum_x, sum_y), (variation, blend)| {
+            let (vx, vy) = variation.apply(x, y);
+            (sum_x + blend * vx, sum_y + blend * vy)
+        })
+    }
+}
+
+/// An iterated function system, chosen from at random in proportion to each transform's weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfsDefinition {
+    pub transforms: Vec<Transform>,
+    cumulative_weights: Vec<f64>,
+}
+
+impl IfsDefinition {
+    pub fn new(transforms: Vec<Transform>) -> Result<Self, String> {
+        if transforms.is_empty() {
+            return Err("an IFS needs at least one transform".to_string());
+        }
+
+        let total: f64 = transforms.iter().map(|t| t.weight).sum();
+        if transforms.iter().any(|t| t.weight < 0.0) || total <= 0.0 {
+            return Err("transform weights must be non-negative and not all zero".to_string());
+        }
+
+        let cumulative_weights = transforms
+            .iter()
+            .scan(0.0, |sum, t| {
+                *sum += t.weight / total;
+                Some(*sum)
+            })
+            .collect();
+
+        Ok(Self { transforms, cumulative_weights })
+    }
+
+    /// Parse a definition with one transform per line:
+    ///
+    /// ```text
+    /// # a     b     c     d     e     f     weight  [color=<0..1>] [<variation>=<blend> ...]
+    ///   0.5   0.0   0.0   0.5   0.0   0.0   1.0     color=0.0 swirl=0.8 linear=0.2
+    /// ```
+    ///
+    /// Blank lines and anything following a `#` are ignored. Transforms without any variations
+    /// are purely affine.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut transforms = Vec::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let transform = parse_transform(line)
+                .map_err(|err| format!("line {}: {}", number + 1, err))?;
+            transforms.push(transform);
+        }
+
+        Self::new(transforms)
+    }
+
+    /// Pick a transform, where `sample` is uniformly distributed in `[0, 1)`
+    fn choose(&self, sample: f64) -> &Transform {
+        let index = self.cumulative_weights.partition_point(|&weight| weight <= sample);
+        &self.transforms[index.min(self.transforms.len() - 1)]
+    }
+
+    /// Find a view that contains the attractor by running a short chaos game.
+    ///
+    /// Returns the `(magnitude, center)` pair accepted by `calculate_region`.
+    pub fn fit_view(&self) -> (f64, (f64, f64)) {
+        let mut rng = Rng::new(0x5eed);
+        let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+        let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+
+        let mut point = (rng.next_f64(), rng.next_f64());
+        for i in 0..20_000 {
+            point = self.choose(rng.next_f64()).apply(point.0, point.1);
+            if !point.0.is_finite() || !point.1.is_finite() {
+                point = (rng.next_f64(), rng.next_f64());
+                continue;
+            }
+            if i >= SETTLE_ITERATIONS {
+                min_x = min_x.min(point.0);
+                max_x = max_x.max(point.0);
+                min_y = min_y.min(point.1);
+                max_y = max_y.max(point.1);
+            }
+        }
+
+        if min_x > max_x {
+            return (4.0, (0.0, 0.0));
+        }
+
+        // Leave a small margin around the attractor
+        let magnitude = (max_x - min_x).max(max_y - min_y).max(f64::EPSILON) * 1.1;
+        (magnitude, ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0))
+    }
+}
+
+fn parse_transform(line: &str) -> Result<Transform, String> {
+    let mut fields = line.split_whitespace();
+
+    let mut numbers = [0.0; 7];
+    for number in numbers.iter_mut() {
+        let field = fields.next().ok_or("expected 6 coefficients and a weight")?;
+        *number = field.parse().map_err(|_| format!("invalid number '{}'", field))?;
+    }
+
+    let mut color = 0.0;
+    let mut variations = Vec::new();
+    for field in fields {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("expected <name>=<value>, found '{}'", field))?;
+        let value: f64 = value.parse().map_err(|_| format!("invalid number '{}'", value))?;
+
+        match key {
+            "color" => color = value,
+            name => match Variation::from_name(name) {
+                Some(variation) => variations.push((variation, value)),
+                None => return Err(format!("unknown variation '{}'", name)),
+            },
+        }
+    }
+
+    if variations.is_empty() {
+        variations.push((Variation::Linear, 1.0));
+    }
+
+    let [a, b, c, d, e, f, weight] = numbers;
+    Ok(Transform { coefficients: [a, b, c, d, e, f], weight, color, variations })
+}
+
+/// A small xorshift generator; the chaos game only needs fast, reproducible noise.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    /// A uniformly distributed value in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Per-pixel hit counts of a chaos game run, and the palette colors accumulated with them when
+/// the colors are wanted
+struct Histogram {
+    counts: Vec<u32>,
+    colors: Option<Vec<[f32; 3]>>,
+}
+
+impl Histogram {
+    fn new(size: usize, colored: bool) -> Self {
+        Self { counts: vec![0; size], colors: colored.then(|| vec![[0.0; 3]; size]) }
+    }
+
+    fn merge(mut self, other: Histogram) -> Self {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        if let (Some(colors), Some(others)) = (&mut self.colors, other.colors) {
+            for (color, other) in colors.iter_mut().zip(others) {
+                for (channel, other) in color.iter_mut().zip(other) {
+                    *channel += other;
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Map a color index in `[0, 1]` to a point on a hue wheel
+fn palette(index: f64) -> [f32; 3] {
+    let (r, g, b) = Hsl::from(index as f32 * 300.0, 85.0, 55.0).to_rgb().as_tuple();
+    [r / 255.0, g / 255.0, b / 255.0]
+}
+
+/// Renders an `IfsDefinition` with the chaos game
+pub struct IfsRenderer {
+    definition: IfsDefinition,
+    iterations: usize,
+    gamma: f64,
+}
+
+impl IfsRenderer {
+    pub fn new(definition: IfsDefinition, iterations: usize, gamma: f64) -> Self {
+        Self { definition, iterations, gamma }
+    }
+
+    /// Run the chaos game over `region` on every available thread, each with its own seed,
+    /// accumulating the palette colors of the points too if `colored`.
+    fn histogram(&self, bounds: Dimensions, region: ComplexRegion, colored: bool) -> Histogram {
+        let runs = rayon::current_num_threads().max(1);
+        let per_run = self.iterations / runs + 1;
+
+        (0..runs)
+            .into_par_iter()
+            .map(|run| {
+                let mut histogram = Histogram::new(bounds.total_pixels(), colored);
+                let mut rng = Rng::new(run as u64 + 1);
+                let mut point = (rng.next_f64() * 2.0 - 1.0, rng.next_f64() * 2.0 - 1.0);
+                let mut color = rng.next_f64();
+
+                for i in 0..per_run + SETTLE_ITERATIONS {
+                    let transform = self.definition.choose(rng.next_f64());
+                    point = transform.apply(point.0, point.1);
+                    color = (color + transform.color) / 2.0;
+
+                    // Points thrown to infinity by a variation restart somewhere random
+                    if !point.0.is_finite() || !point.1.is_finite() {
+                        point = (rng.next_f64() * 2.0 - 1.0, rng.next_f64() * 2.0 - 1.0);
+                        continue;
+                    }
+                    if i < SETTLE_ITERATIONS {
+                        continue;
+                    }
+
+                    let c = Complex::new(point.0, point.1);
+                    if let Some(pixel) = region.point_to_pixel(bounds, c) {
+                        let index = pixel.y * bounds.width + pixel.x;
+                        histogram.counts[index] += 1;
+                        if let Some(colors) = &mut histogram.colors {
+                            for (channel, value) in colors[index].iter_mut().zip(palette(color)) {
+                                *channel += value;
+                            }
+                        }
+                    }
+                }
+                histogram
+            })
+            .reduce(|| Histogram::new(bounds.total_pixels(), colored), Histogram::merge)
+    }
+
+    /// Render a plain IFS into a grayscale buffer, lighting every pixel the attractor visits.
+    pub fn render(&self, pixels: &mut [u8], bounds: Dimensions, region: ComplexRegion) {
+        assert_eq!(pixels.len(), bounds.total_pixels());
+
+        let histogram = self.histogram(bounds, region, false);
+        for (pixel, count) in pixels.iter_mut().zip(histogram.counts) {
+            *pixel = if count > 0 { u8::MAX } else { 0 };
+        }
+    }
+
+    /// Render a fractal flame into an RGB buffer.
+    ///
+    /// Brightness comes from the log of each pixel's hit count relative to the busiest pixel,
+    /// corrected by `gamma`, and hue from the average color index of the points that landed there.
+    pub fn render_flame(&self, pixels: &mut [u8], bounds: Dimensions, region: ComplexRegion) {
+        assert_eq!(pixels.len(), bounds.total_pixels() * 3);
+
+        let histogram = self.histogram(bounds, region, true);
+        let max_count = histogram.counts.iter().copied().max().unwrap_or(0);
+        if max_count == 0 {
+            pixels.fill(0);
+            return;
+        }
+        let log_max = (1.0 + max_count as f64).ln();
+
+        let colors = histogram.colors.expect("a flame histogram is colored");
+        for ((rgb, count), color) in pixels.chunks_mut(3).zip(histogram.counts).zip(colors) {
+            if count == 0 {
+                rgb.fill(0);
+                continue;
+            }
+            let alpha = (1.0 + count as f64).ln() / log_max;
+            let brightness = alpha.powf(1.0 / self.gamma);
+            for (channel, sum) in rgb.iter_mut().zip(color) {
+                let average = sum as f64 / count as f64;
+                *channel = (average * brightness * u8::MAX as f64).round().min(255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Work out the region to render, fitting the attractor when no center or zoom was given
+pub fn ifs_region(
+    definition: &IfsDefinition, zoom: Option<f64>, center: Option<(f64, f64)>,
+) -> ComplexRegion {
+    let (fit_zoom, fit_center) = definition.fit_view();
+    calculate_region(zoom.unwrap_or(fit_zoom), center.unwrap_or(fit_center))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIERPINSKI: &str = "
+        # Sierpinski triangle
+        0.5 0.0 0.0 0.5 0.0  0.0 1.0
+        0.5 0.0 0.0 0.5 0.5  0.0 1.0
+        0.5 0.0 0.0 0.5 0.25 0.5 1.0  # top
+    ";
+
+    #[test]
+    fn test_parse_definition() {
+        let definition = IfsDefinition::parse(SIERPINSKI).unwrap();
+        assert_eq!(definition.transforms.len(), 3);
+        assert_eq!(definition.transforms[2].coefficients, [0.5, 0.0, 0.0, 0.5, 0.25, 0.5]);
+        assert_eq!(definition.transforms[0].variations, vec![(Variation::Linear, 1.0)]);
+    }
+
+    #[test]
+    fn test_parse_flame_transform() {
+        let definition =
+            IfsDefinition::parse("1 0 0 1 0 0 2 color=0.75 swirl=0.5 spherical=0.25").unwrap();
+        let transform = &definition.transforms[0];
+        assert_eq!(transform.weight, 2.0);
+        assert_eq!(transform.color, 0.75);
+        assert_eq!(
+            transform.variations,
+            vec![(Variation::Swirl, 0.5), (Variation::Spherical, 0.25)]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(IfsDefinition::parse("").is_err());
+        assert!(IfsDefinition::parse("1 0 0 1 0").is_err());
+        assert!(IfsDefinition::parse("1 0 0 1 0 x 1").is_err());
+        assert!(IfsDefinition::parse("1 0 0 1 0 0 1 wobble=1").is_err());
+        assert!(IfsDefinition::parse("1 0 0 1 0 0 0").is_err());
+        assert_eq!(
+            IfsDefinition::parse("1 0 0 1 0 0 1\n1 0 0 1 0 0 1 swirl").unwrap_err(),
+            "line 2: expected <name>=<value>, found 'swirl'"
+        );
+    }
+
+    #[test]
+    fn test_choose_by_weight() {
+        let definition = IfsDefinition::parse("1 0 0 1 0 0 1\n1 0 0 1 1 0 3").unwrap();
+        assert_eq!(definition.choose(0.0).coefficients[4], 0.0);
+        assert_eq!(definition.choose(0.24).coefficients[4], 0.0);
+        assert_eq!(definition.choose(0.26).coefficients[4], 1.0);
+        assert_eq!(definition.choose(0.999).coefficients[4], 1.0);
+    }
+
+    #[test]
+    fn test_variations() {
+        assert_eq!(Variation::Linear.apply(0.5, -2.0), (0.5, -2.0));
+        assert_eq!(Variation::Sinusoidal.apply(0.0, 0.0), (0.0, 0.0));
+
+        let (x, y) = Variation::Spherical.apply(2.0, 0.0);
+        assert!((x - 0.5).abs() < 1e-12 && y == 0.0);
+
+        // Polar maps the positive y axis to theta = 0
+        let (x, y) = Variation::Polar.apply(0.0, 3.0);
+        assert!(x.abs() < 1e-12 && (y - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_render_sierpinski() {
+        let definition = IfsDefinition::parse(SIERPINSKI).unwrap();
+        let renderer = IfsRenderer::new(definition, 50_000, 2.2);
+        let bounds = Dimensions::new(32, 32);
+        let region = ComplexRegion::new(Complex::new(0.0, 1.0), Complex::new(1.0, 0.0));
+        let mut pixels = vec![0u8; bounds.total_pixels()];
+
+        renderer.render(&mut pixels, bounds, region);
+
+        // The bottom corners belong to the triangle, the hole in its middle does not
+        assert_eq!(pixels[31 * 32], u8::MAX);
+        assert_eq!(pixels[31 * 32 + 31], u8::MAX);
+        assert_eq!(pixels[24 * 32 + 16], 0);
+        assert_eq!(pixels[0], 0);
+    }
+
+    #[test]
+    fn test_fit_view() {
+        let definition = IfsDefinition::parse(SIERPINSKI).unwrap();
+        let (magnitude, (x, y)) = definition.fit_view();
+        assert!((magnitude - 1.1).abs() < 0.01);
+        assert!((x - 0.5).abs() < 0.01);
+        assert!((y - 0.5).abs() < 0.01);
+    }
+}
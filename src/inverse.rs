@@ -0,0 +1,136 @@
+use crate::algorithms::{EscapeTime, PlottingAlgorithm};
+use crate::mandelbrot::ComplexRegion;
+use crate::types::Dimensions;
+use num::complex::Complex;
+use std::collections::HashMap;
+
+/// Plots the boundary of the Julia set of `z² + c` with the modified inverse iteration method
+/// (MIIM).
+///
+/// Every point of the Julia set has its preimages `±sqrt(z - c)` in the set too, and they spread
+/// over all of it, so the tree of preimages of one point on the set reaches every part of the
+/// boundary, however thin. Plain inverse iteration follows random branches and crowds into the
+/// parts that attract preimages; MIIM walks the whole tree instead, pruning each branch that
+/// lands on a cell of the image already visited `max_hits` times.
+pub struct InverseIteration {
+    pub c: Complex<f64>,
+    /// Visits allowed per pixel-sized cell before branches through it are pruned
+    pub max_hits: u32,
+    /// Upper bound on the points visited, for sets whose boundary covers a huge number of cells
+    pub max_points: usize,
+}
+
+impl InverseIteration {
+    /// Set each pixel of `pixels`, an image of `region`, that the boundary passes through to
+    /// `value`. Returns the number of points visited.
+    ///
+    /// Cells outside of the image are counted too, so branches that leave it and come back are
+    /// followed without running away.
+    pub fn plot(
+        &self, pixels: &mut [u8], bounds: Dimensions, region: ComplexRegion, value: u8,
+    ) -> usize {
+        assert_eq!(pixels.len(), bounds.total_pixels());
+
+        let mut hits: HashMap<(i64, i64), u32> = HashMap::new();
+        let mut stack = vec![repelling_fixed_point(self.c)];
+        let mut visited = 0;
+
+        while let Some(z) = stack.pop() {
+            if visited == self.max_points {
+                break;
+            }
+            let (x, y) = region.point_to_position(bounds, z);
+            if !x.is_finite() || !y.is_finite() {
+                continue;
+            }
+            let count = hits.entry((x.floor() as i64, y.floor() as i64)).or_insert(0);
+            if *count >= self.max_hits {
+                continue;
+            }
+            *count += 1;
+            visited += 1;
+
+            if x >= 0.0 && y >= 0.0 && x < bounds.width as f64 && y < bounds.height as f64 {
+                pixels[y as usize * bounds.width + x as usize] = value;
+            }
+
+            let preimage = (z - self.c).sqrt();
+            stack.push(preimage);
+            stack.push(-preimage);
+        }
+
+        visited
+    }
+}
+
+/// The fixed point of `z² + c` that the external ray at angle 0 lands on. It lies on the Julia
+/// set, and is repelling for every `c` but `1/4`.
+pub fn repelling_fixed_point(c: Complex<f64>) -> Complex<f64> {
+    (1.0 + (1.0 - 4.0 * c).sqrt()) / 2.0
+}
+
+/// Whether the Julia set of `z² + c` is connected, which it is exactly when the critical orbit
+/// stays bounded; otherwise it is a Cantor set. Orbits that last `limit` iterations are taken to
+/// be bounded.
+pub fn is_connected(c: Complex<f64>, limit: usize) -> bool {
+    EscapeTime::default().calculate(c, 0, limit).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot::calculate_region;
+
+    #[test]
+    fn test_repelling_fixed_point() {
+        for c in [Complex::new(0.0, 0.0), Complex::new(-0.8, 0.156), Complex::new(0.5, 0.5)] {
+            let z = repelling_fixed_point(c);
+            assert!((z * z + c - z).norm() < 1e-12);
+            assert!((2.0 * z).norm() > 1.0);
+        }
+    }
+
+    #[test]
+    fn test_is_connected() {
+        assert!(is_connected(Complex::new(0.0, 0.0), 1000));
+        assert!(is_connected(Complex::new(-1.0, 0.0), 1000));
+        assert!(!is_connected(Complex::new(0.5, 0.5), 1000));
+    }
+
+    #[test]
+    fn test_plot_unit_circle() {
+        // The Julia set of 0 is the unit circle
+        let miim = InverseIteration { c: Complex::new(0.0, 0.0), max_hits: 2, max_points: 1000000 };
+        let bounds = Dimensions::new(101, 101);
+        let region = calculate_region(2.5, (0.0, 0.0));
+        let mut pixels = vec![0; bounds.total_pixels()];
+        let visited = miim.plot(&mut pixels, bounds, region, u8::MAX);
+
+        let pixel_size = 2.5 / 101.0;
+        let mut lit = 0;
+        for (index, _) in pixels.iter().enumerate().filter(|(_, &p)| p == u8::MAX) {
+            let pixel = (index % bounds.width, index / bounds.width).into();
+            let z = region.pixel_to_point(bounds, pixel);
+            assert!((z.norm() - 1.0).abs() < 2.0 * pixel_size);
+            lit += 1;
+        }
+        // Most of the circle is covered, and density limiting stops the walk early
+        assert!(lit > 250);
+        assert!(visited < 2 * 2 * 1000);
+    }
+
+    #[test]
+    fn test_plot_cantor_dust() {
+        // The Julia set of a point far outside the Mandelbrot set is dust, which escape time
+        // renders as almost nothing
+        let c = Complex::new(0.5, 0.5);
+        let miim = InverseIteration { c, max_hits: 1, max_points: 100000 };
+        let bounds = Dimensions::new(64, 64);
+        let mut pixels = vec![0; bounds.total_pixels()];
+        miim.plot(&mut pixels, bounds, calculate_region(3.0, (0.0, 0.0)), 1);
+        assert!(pixels.iter().filter(|&&p| p == 1).count() > 50);
+
+        let miim = InverseIteration { max_points: 10, ..miim };
+        assert_eq!(miim.plot(&mut pixels, bounds, calculate_region(3.0, (0.0, 0.0)), 1), 10);
+    }
+}
@@ -0,0 +1,26 @@
+//! The fractal renderer behind the `mandelbrot` command, as a library for the benchmarks and
+//! anything else that wants to draw with it
+
+pub mod algorithms;
+pub mod analysis;
+pub mod atlas;
+pub mod coloring;
+pub mod contour;
+pub mod font;
+pub mod histogram;
+pub mod ifs;
+pub mod inverse;
+pub mod lanes;
+pub mod mandelbrot;
+pub mod mesh;
+pub mod montage;
+pub mod orbit;
+pub mod overlay;
+pub mod parsers;
+pub mod projection;
+pub mod raymarch;
+pub mod rays;
+pub mod sweep;
+pub mod traps;
+pub mod types;
+pub mod zoom;
@@ -1,14 +1,21 @@
-mod mandelbrot;
-mod parsers;
-mod algorithms;
+use ::mandelbrot::{
+    algorithms, analysis, atlas, coloring, contour, histogram, ifs, inverse, mandelbrot, mesh,
+    orbit, overlay, parsers, projection, raymarch, rays, sweep, traps, types, zoom,
+};
+use algorithms::{Algorithm, PlottingAlgorithm, WithAlgorithm};
 use mandelbrot::Canvas;
+use projection::Projection;
 mod cli;
-use cli::Arguments;
-mod types;
+use cli::{
+    AnalyzeArguments, Arguments, AtlasArguments, Command, IfsArguments, InverseArguments,
+    OrbitArguments, RaymarchArguments, RenderArguments, SweepArguments, ZoomArguments,
+};
+use coloring::{Coloring, Decomposition};
+use histogram::Histogram;
+use num::Complex;
 
 // #[macro_use]
 extern crate clap;
-use clap::Parser;
 
 #[cfg(feature = "gpu")]
 extern crate ocl;
@@ -23,23 +30,422 @@ use image::ColorType;
 use std::fs::File;
 use rayon::prelude::*;
 
+/// Points traced per dwell band of an external ray
+const RAY_SHARPNESS: usize = 8;
+
 /// Write the buffer `pixels`, whose dimensions are given by `bounds` to the file name `filename`
 fn write_image(
-    filename: &str, pixels: &[u8], bounds: (usize, usize),
+    filename: &str, pixels: &[u8], bounds: (usize, usize), color: ColorType,
 ) -> Result<(), std::io::Error> {
     let output = File::create(filename)?;
 
     let encoder = PNGEncoder::new(output);
-    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::Gray(8))?;
+    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, color)?;
 
     Ok(())
 }
 
+fn main() {
+    let args = Arguments::parse_with_params();
 
+    match args.command {
+        Some(Command::Ifs(ifs_args)) => render_ifs(&ifs_args),
+        Some(Command::Analyze(analyze_args)) => analyze(&analyze_args),
+        Some(Command::Orbit(orbit_args)) => inspect_orbit(&orbit_args),
+        Some(Command::Atlas(atlas_args)) => render_atlas(&atlas_args),
+        Some(Command::Sweep(sweep_args)) => render_sweep(&sweep_args),
+        Some(Command::Inverse(inverse_args)) => render_inverse(&inverse_args),
+        Some(Command::Raymarch(raymarch_args)) => render_raymarch(&raymarch_args),
+        Some(Command::Zoom(zoom_args)) => render_zoom(&zoom_args),
+        None => render(&args.render.expect("render arguments are required without a subcommand")),
+    }
+}
 
-fn main() {
-    let args = Arguments::parse();
-    let (upper_left, lower_right) = mandelbrot::calculate_corners(args.zoom, args.center);
+/// Render the chaos game of an IFS definition file, as a fractal flame if requested
+fn render_ifs(args: &IfsArguments) {
+    let source = std::fs::read_to_string(&args.definition).expect("error reading IFS definition");
+    let definition = match ifs::IfsDefinition::parse(&source) {
+        Ok(definition) => definition,
+        Err(err) => {
+            eprintln!("error parsing {}: {}", args.definition, err);
+            std::process::exit(1);
+        }
+    };
+    let region = ifs::ifs_region(&definition, args.zoom, args.center);
+    let bounds = types::Dimensions::from(args.dimensions);
+    let renderer = ifs::IfsRenderer::new(definition, args.iterations, args.gamma);
+
+    if args.flame {
+        let mut pixels = vec![0; bounds.total_pixels() * 3];
+        renderer.render_flame(&mut pixels, bounds, region);
+        write_image(&args.output, &pixels, args.dimensions, ColorType::RGB(8))
+            .expect("error writing PNG file");
+    } else {
+        let mut pixels = vec![0; bounds.total_pixels()];
+        renderer.render(&mut pixels, bounds, region);
+        write_image(&args.output, &pixels, args.dimensions, ColorType::Gray(8))
+            .expect("error writing PNG file");
+    }
+}
+
+/// Find the component (or Misiurewicz point) nearest the given point and print where it is
+fn analyze(args: &AnalyzeArguments) {
+    let seed = Complex::new(args.center.0, args.center.1);
+    if let (Some(preperiod), Some(period)) = (args.preperiod, args.period) {
+        return analyze_misiurewicz(seed, preperiod, period);
+    }
+
+    let period = match (args.period, args.zoom) {
+        (Some(period), _) => period,
+        (None, Some(zoom)) => {
+            let region = mandelbrot::calculate_region(zoom, args.center);
+            match analysis::find_period(region, args.limit) {
+                Some(period) => period,
+                None => {
+                    eprintln!("error: no period up to {} found in the region", args.limit);
+                    std::process::exit(1);
+                }
+            }
+        }
+        (None, None) => unreachable!("clap requires --zoom without --period"),
+    };
+
+    let Some(nucleus) = analysis::find_nucleus(seed, period) else {
+        eprintln!("error: Newton's method did not converge on a nucleus of period {}", period);
+        std::process::exit(1);
+    };
+    let component = analysis::Component::new(nucleus, period);
+
+    println!("nucleus: {},{}", component.nucleus.re, component.nucleus.im);
+    println!("period: {}", component.period);
+    println!("size: {:e}", component.size);
+    println!("orientation: {:.3} degrees", component.orientation.to_degrees());
+    if args.frame {
+        println!(
+            "--center={},{} --zoom={:e}",
+            component.nucleus.re,
+            component.nucleus.im,
+            component.frame_zoom()
+        );
+    }
+}
+
+/// Find the Misiurewicz point nearest `seed` and print it after checking it
+fn analyze_misiurewicz(seed: Complex<f64>, preperiod: usize, period: usize) {
+    let Some(point) = analysis::find_misiurewicz(seed, preperiod, period) else {
+        eprintln!(
+            "error: Newton's method did not converge on a Misiurewicz point of preperiod {} and \
+             period {}",
+            preperiod, period
+        );
+        std::process::exit(1);
+    };
+
+    match analysis::verify_misiurewicz(point, preperiod, period) {
+        Ok(residual) => {
+            println!("misiurewicz point: {},{}", point.re, point.im);
+            println!("preperiod: {}", preperiod);
+            println!("period: {}", period);
+            println!("check: passed, cycle residual {:e}", residual);
+            println!("--center={},{}", point.re, point.im);
+        }
+        Err(err) => {
+            eprintln!("error: found {},{} but it failed the check: {}", point.re, point.im, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write out the orbit of a single point, and draw it over a render if requested
+fn inspect_orbit(args: &OrbitArguments) {
+    let c = Complex::new(args.point.0, args.point.1);
+    let algorithm = match algorithms::get_plotting_algorithm(
+        &args.algorithm,
+        args.escape.escape(&args.algorithm),
+        &args.formula.parameters(),
+    ) {
+        Ok(algorithm) => algorithm,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let z0 = args.z0.map_or(algorithm.critical_point(), |(re, im)| Complex::new(re, im));
+    let report = orbit::OrbitReport::trace(algorithm.as_ref(), z0, c, args.limit);
+
+    let data = match args.format.as_str() {
+        "json" => report.to_json(),
+        _ => report.to_csv(),
+    };
+    let summary = format!(
+        "escape: {}\nperiod: {}\nmin |z|: {}\nmax |z|: {}",
+        report.escape.map_or("none".to_string(), |n| n.to_string()),
+        report.period.map_or("none".to_string(), |n| n.to_string()),
+        report.min_norm,
+        report.max_norm
+    );
+    // Keep standard output to the orbit itself when that is where it goes
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, data).expect("error writing orbit");
+            println!("{}", summary);
+        }
+        None => {
+            print!("{}", data);
+            eprintln!("{}", summary);
+        }
+    }
+
+    if let Some(path) = &args.render {
+        let algorithm = match args.z0 {
+            Some(_) => Box::new(algorithms::Julia { algorithm, c }),
+            None => algorithm,
+        };
+        let canvas = Canvas::new(algorithm, Coloring::Iterations);
+        let (upper_left, lower_right) = mandelbrot::calculate_corners(args.zoom, args.center);
+        let mut pixels = vec![0; args.dimensions.0 * args.dimensions.1];
+        for_each_band(
+            &mut pixels,
+            args.dimensions,
+            upper_left,
+            lower_right,
+            |band, band_bounds, band_upper_left, band_lower_right| {
+                canvas.render(
+                    band,
+                    band_bounds,
+                    band_upper_left,
+                    band_lower_right,
+                    args.limit,
+                    false,
+                );
+            },
+        );
+
+        let region = mandelbrot::calculate_region(args.zoom, args.center);
+        let bounds = types::Dimensions::from(args.dimensions);
+        overlay::draw_polyline(&mut pixels, bounds, region, &report.points, u8::MAX);
+        write_image(path, &pixels, args.dimensions, ColorType::Gray(8))
+            .expect("error writing PNG file");
+    }
+}
+
+/// Render a montage of Julia sets sampled over the parameter plane
+fn render_atlas(args: &AtlasArguments) {
+    let atlas = atlas::Atlas {
+        algorithm: args.algorithm.clone(),
+        escape: args.escape.escape(&args.algorithm),
+        parameters: args.formula.parameters(),
+        julia_zoom: args.julia_zoom,
+        limit: args.limit,
+        backdrop: args.backdrop,
+    };
+    let region = mandelbrot::calculate_region(args.zoom, args.center);
+    let montage = match atlas.render(region, args.grid.into(), args.tile.into()) {
+        Ok(montage) => montage,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let bounds = montage.bounds();
+    write_image(&args.output, &montage.pixels, (bounds.width, bounds.height), ColorType::Gray(8))
+        .expect("error writing PNG file");
+}
+
+/// Render a contact sheet of variants with one parameter swept across them
+fn render_sweep(args: &SweepArguments) {
+    let sweep = match sweep_parameter(args) {
+        Ok(sweep) => sweep,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let base = sweep::Variant {
+        limit: args.limit,
+        exponent: args.exponent,
+        escape: args.escape.escape(&args.algorithm),
+        parameters: args.formula.parameters(),
+        julia: None,
+    };
+    let variants = sweep.variants(&base, args.steps);
+    let coloring = coloring::get_coloring(&args.coloring, 0.0);
+    let region = mandelbrot::calculate_region(args.zoom, args.center);
+
+    let montage = match sweep::render_contact_sheet(
+        &sweep,
+        &variants,
+        &args.algorithm,
+        &coloring,
+        region,
+        args.tile.into(),
+        args.columns,
+    ) {
+        Ok(montage) => montage,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let bounds = montage.bounds();
+    write_image(&args.output, &montage.pixels, (bounds.width, bounds.height), ColorType::Gray(8))
+        .expect("error writing PNG file");
+}
+
+/// Work out what is swept from the sweep arguments
+fn sweep_parameter(args: &SweepArguments) -> Result<sweep::Sweep, String> {
+    // Other exponents draw a Multibrot set in place of the algorithm
+    if args.exponent != 2.0 && args.algorithm != "escape_time" {
+        return Err("--exponent only applies to the escape_time algorithm".to_string());
+    }
+    if args.parameter == "julia" {
+        if args.path_point.len() < 2 {
+            return Err("a julia sweep needs at least two --path-point values".to_string());
+        }
+        let path = args.path_point.iter().map(|&(re, im)| Complex::new(re, im)).collect();
+        return Ok(sweep::Sweep::Julia(path));
+    }
+
+    let (Some(from), Some(to)) = (args.from, args.to) else {
+        return Err(format!("a {} sweep needs --from and --to", args.parameter));
+    };
+    match args.parameter.as_str() {
+        "limit" if from >= 1.0 && to >= 1.0 => Ok(sweep::Sweep::Limit(from as usize, to as usize)),
+        "limit" => Err("iteration limits must be at least 1".to_string()),
+        "radius" if from > 1.0 && to > 1.0 => Ok(sweep::Sweep::Radius(from, to)),
+        "radius" => Err("escape radii must be greater than 1".to_string()),
+        _ if args.algorithm != "escape_time" => {
+            Err("exponent sweeps only apply to the escape_time algorithm".to_string())
+        }
+        _ => Ok(sweep::Sweep::Exponent(from, to)),
+    }
+}
+
+/// Plot a Julia set boundary by inverse iteration, reporting whether the set is connected
+fn render_inverse(args: &InverseArguments) {
+    let c = Complex::new(args.point.0, args.point.1);
+    let miim =
+        inverse::InverseIteration { c, max_hits: args.max_hits, max_points: args.max_points };
+    let region = mandelbrot::calculate_region(args.zoom, args.center);
+    let bounds = types::Dimensions::from(args.dimensions);
+    let mut pixels = vec![0; bounds.total_pixels()];
+    let visited = miim.plot(&mut pixels, bounds, region, u8::MAX);
+
+    println!("connected: {}", if inverse::is_connected(c, args.limit) { "yes" } else { "no" });
+    println!("points: {}", visited);
+    write_image(&args.output, &pixels, args.dimensions, ColorType::Gray(8))
+        .expect("error writing PNG file");
+}
+
+/// Ray march the 3D fractal described by the raymarch arguments
+fn render_raymarch(args: &RaymarchArguments) {
+    let scene = match raymarch_scene(args) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let bounds = types::Dimensions::from(args.dimensions);
+    let mut pixels = vec![0; bounds.total_pixels() * 3];
+    scene.render(&mut pixels, bounds);
+    write_image(&args.output, &pixels, args.dimensions, ColorType::RGB(8))
+        .expect("error writing PNG file");
+}
+
+/// Set up the scene from the raymarch arguments
+fn raymarch_scene(args: &RaymarchArguments) -> Result<raymarch::Scene, String> {
+    use raymarch::{Camera, Scene, Shape, Vec3};
+
+    if args.dimensions.0 == 0 || args.dimensions.1 == 0 {
+        return Err("--dimensions must be at least 1x1".to_string());
+    }
+    if !(args.fov > 0.0 && args.fov < 180.0) {
+        return Err("--fov must be between 0 and 180 degrees".to_string());
+    }
+    let shape = match args.shape.as_str() {
+        "quaternion" => Shape::QuaternionJulia { c: args.julia_c, iterations: args.iterations },
+        _ => Shape::Mandelbulb { power: args.power, iterations: args.iterations },
+    };
+    let [x, y, z] = args.camera;
+    let position = Vec3::new(x, y, z);
+    let [x, y, z] = args.target;
+    let target = Vec3::new(x, y, z);
+    // With no direction to look in, every ray would be NaN and miss the shape
+    if (target - position).length() == 0.0 {
+        return Err("--camera and --target must be different points".to_string());
+    }
+
+    Ok(Scene {
+        shape,
+        camera: Camera { position, target, field_of_view: args.fov },
+        max_steps: args.max_steps,
+        shadow_softness: args.shadow_softness,
+        occlusion: args.ao_strength,
+    })
+}
+
+/// Render a zoom video's frames from an exponential map strip, which computes each orbit once
+/// rather than once per frame it shows up in
+fn render_zoom(args: &ZoomArguments) {
+    let plotter = match algorithms::get_plotting_algorithm(
+        &args.algorithm,
+        args.escape.escape(&args.algorithm),
+        &args.formula.parameters(),
+    ) {
+        Ok(plotter) => plotter,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let (default_zoom, default_center) = plotter.default_view();
+    let (re, im) = args.center.unwrap_or(default_center);
+    let zoom = zoom::Zoom {
+        center: Complex::new(re, im),
+        start: args.start_zoom.unwrap_or(default_zoom),
+        end: args.end_zoom,
+        frames: args.frames,
+    };
+
+    let canvas = Canvas::new(plotter, coloring::get_coloring(&args.coloring, 0.0));
+    let bounds = types::Dimensions::from(args.dimensions);
+    let (map, rows) = zoom.strip(bounds);
+    let strip = zoom::render_strip(&canvas, &map, rows, args.limit, args.invert);
+    let filename = format!("{}_strip.png", args.output);
+    write_image(&filename, &strip, (map.width, rows), ColorType::Gray(8))
+        .expect("error writing PNG file");
+    println!(
+        "strip: {}x{} ({} orbits, against {} for the frames)",
+        map.width,
+        rows,
+        strip.len(),
+        bounds.total_pixels() * args.frames
+    );
+
+    let mut frame = vec![0; bounds.total_pixels()];
+    for index in 0..args.frames {
+        zoom::reconstruct(&strip, &map, &mut frame, bounds, zoom.region(index));
+        let filename = format!("{}_{:04}.png", args.output, index);
+        write_image(&filename, &frame, args.dimensions, ColorType::Gray(8))
+            .expect("error writing PNG file");
+    }
+}
+
+/// Render the escape time fractal described by the top level arguments
+fn render(args: &RenderArguments) {
+    let algorithm = match Algorithm::new(&args.algorithm, args.escape(), &args.parameters()) {
+        Ok(algorithm) => algorithm,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let (default_zoom, default_center) = algorithm.as_dyn().default_view();
+    let (zoom, center) = (args.zoom.unwrap_or(default_zoom), args.center.unwrap_or(default_center));
+    let (upper_left, lower_right) = mandelbrot::calculate_corners(zoom, center);
 
     if cfg!(feature = "gpu") && args.gpu {
         #[cfg(feature = "gpu")]
@@ -53,40 +459,395 @@ fn main() {
         }
     } else {
         let mut pixels = vec![0; args.dimensions.0 * args.dimensions.1];
-        let plotter = crate::algorithms::get_plotting_algorithm(&args.algorithm);
-        let canvas = Canvas::new(plotter);
-        
-        let bands: Vec<(usize, &mut [u8])> = pixels
-            .chunks_mut(args.dimensions.0)
-            .enumerate()
-            .collect();
-
-        bands.into_par_iter().for_each(|(i, band)| {
-            let top = i;
-            let band_bounds = (args.dimensions.0, 1);
-            let band_upper_left = mandelbrot::pixel_to_point(
-                args.dimensions,
-                (0, top),
-                upper_left,
-                lower_right,
-            );
-            let band_lower_right = mandelbrot::pixel_to_point(
+        let coloring = match render_coloring(args) {
+            Ok(coloring) => coloring,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(2);
+            }
+        };
+        let projection = match render_projection(args, zoom, center) {
+            Ok(projection) => projection,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(2);
+            }
+        };
+        let mesh_format = match mesh_format(args) {
+            Ok(format) => format,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                std::process::exit(2);
+            }
+        };
+        if !args.trace_ray.is_empty() && args.algorithm != "escape_time" {
+            eprintln!("error: --trace-ray only follows the rays of the escape_time algorithm");
+            std::process::exit(2);
+        }
+        if args.histogram && !["iterations", "smooth"].contains(&args.coloring.as_str()) {
+            eprintln!("error: --histogram only applies to the iterations and smooth colorings");
+            std::process::exit(2);
+        }
+        algorithm.dispatch(RenderImage {
+            args,
+            coloring,
+            projection,
+            pixels: &mut pixels,
+            upper_left,
+            lower_right,
+        });
+
+        if let (Some(path), Some(format)) = (&args.mesh, mesh_format) {
+            export_mesh(args, algorithm.as_dyn(), path, format, upper_left, lower_right);
+        }
+        if let Some(path) = &args.svg {
+            export_svg(args, algorithm.as_dyn(), path, upper_left, lower_right);
+        }
+
+        draw_rays(args, mandelbrot::calculate_region(zoom, center), &mut pixels);
+
+        write_image(&args.output, &pixels, args.dimensions, ColorType::Gray(8))
+            .expect("error writing PNG file");
+    }
+}
+
+/// The drawing part of `render`, compiled once for each formula `Algorithm::dispatch` can pick
+struct RenderImage<'a> {
+    args: &'a RenderArguments,
+    coloring: Coloring,
+    projection: Option<Box<dyn Projection + Sync>>,
+    pixels: &'a mut [u8],
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+}
+
+impl WithAlgorithm for RenderImage<'_> {
+    type Output = ();
+
+    fn run<A: PlottingAlgorithm + Send + Sync + ?Sized>(self, algorithm: &A) {
+        let Self { args, coloring, projection, pixels, upper_left, lower_right } = self;
+        let canvas = Canvas::new(algorithm, coloring);
+
+        if let Some(projection) = projection {
+            let bounds = types::Dimensions::from(args.dimensions);
+            pixels.par_chunks_mut(bounds.width).enumerate().for_each(|(row, line)| {
+                canvas.render_projected(
+                    line,
+                    bounds,
+                    row,
+                    projection.as_ref(),
+                    args.limit,
+                    args.invert,
+                );
+            });
+        } else if args.histogram {
+            render_equalized(args, &canvas, pixels, upper_left, lower_right);
+        } else {
+            for_each_band(
+                pixels,
                 args.dimensions,
-                (args.dimensions.0, top + 1),
                 upper_left,
                 lower_right,
+                |band, band_bounds, band_upper_left, band_lower_right| {
+                    canvas.render(
+                        band,
+                        band_bounds,
+                        band_upper_left,
+                        band_lower_right,
+                        args.limit,
+                        args.invert,
+                    );
+                },
             );
+        }
+    }
+}
+
+/// Draw the external rays requested with `--trace-ray` over the rendered image
+fn draw_rays(args: &RenderArguments, region: mandelbrot::ComplexRegion, pixels: &mut [u8]) {
+    let bounds = types::Dimensions::from(args.dimensions);
+    let value = if args.invert { 0 } else { u8::MAX };
+
+    for angle in &args.trace_ray {
+        let points = rays::trace_ray(*angle, args.ray_depth, RAY_SHARPNESS);
+        overlay::draw_polyline(pixels, bounds, region, &points, value);
+    }
+}
+
+/// Check the mesh arguments before anything is rendered, working out the format of the mesh
+/// from the extension of `--mesh`; `None` when no mesh was asked for
+fn mesh_format(args: &RenderArguments) -> Result<Option<mesh::MeshFormat>, String> {
+    let path = match &args.mesh {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let format = mesh::MeshFormat::from_path(path)?;
+    if args.mesh_base.is_some_and(|thickness| thickness <= 0.0) {
+        return Err("--mesh-base must be positive".to_string());
+    }
+    if args.dimensions.0 < 2 || args.dimensions.1 < 2 {
+        return Err("--mesh needs an image of at least 2x2 pixels".to_string());
+    }
+    Ok(Some(format))
+}
 
-            canvas.render(
+/// Export the smooth iteration or distance field over the pixels of the render as a mesh
+fn export_mesh(
+    args: &RenderArguments, plotter: &(dyn PlottingAlgorithm + Send + Sync), path: &str,
+    format: mesh::MeshFormat, upper_left: Complex<f64>, lower_right: Complex<f64>,
+) {
+    let values = render_field(args, plotter, Coloring::Smooth, upper_left, lower_right);
+    let bounds = types::Dimensions::from(args.dimensions);
+    let field = match args.mesh_field.as_str() {
+        "distance" => mesh::Field::Distance,
+        _ => mesh::Field::Smooth,
+    };
+    let elevations = field.elevations(&values, bounds);
+    let heightmap = mesh::Heightmap::new(&elevations, bounds, args.height_scale, args.height_clamp);
+    let mesh = heightmap.mesh(args.decimate, args.mesh_base);
+
+    let mut output = std::io::BufWriter::new(File::create(path).expect("error creating mesh file"));
+    mesh.write(format, &mut output).expect("error writing mesh file");
+    println!("mesh: {} vertices, {} triangles", mesh.vertices.len(), mesh.triangles.len());
+}
+
+/// Export the contours of the iteration bands, or isolines of the smooth iteration count, as SVG
+fn export_svg(
+    args: &RenderArguments, plotter: &(dyn PlottingAlgorithm + Send + Sync), path: &str,
+    upper_left: Complex<f64>, lower_right: Complex<f64>,
+) {
+    let style = match args.isolines.is_empty() {
+        true => contour::Style::Bands,
+        false => contour::Style::Isolines,
+    };
+    let coloring = match style {
+        contour::Style::Isolines => Coloring::Smooth,
+        contour::Style::Bands => Coloring::Iterations,
+    };
+    let values = render_field(args, plotter, coloring, upper_left, lower_right);
+    let levels = match style {
+        contour::Style::Isolines => args.isolines.clone(),
+        contour::Style::Bands => contour::band_levels(&values, args.band_width, args.limit),
+    };
+
+    let bounds = types::Dimensions::from(args.dimensions);
+    let contours: Vec<Vec<contour::Contour>> = contour::trace(&values, bounds, &levels)
+        .into_iter()
+        .map(|contours| contour::clean(contours, args.simplify, args.min_contour_area))
+        .collect();
+    // Levels no band reaches would only add empty paths
+    let contours: Vec<_> = match style {
+        contour::Style::Bands => contours.into_iter().filter(|c| !c.is_empty()).collect(),
+        contour::Style::Isolines => contours,
+    };
+
+    let region = mandelbrot::ComplexRegion::new(upper_left, lower_right);
+    let mut output = std::io::BufWriter::new(File::create(path).expect("error creating SVG file"));
+    contour::write_svg(&mut output, bounds, region, &contours, style)
+        .expect("error writing SVG file");
+    println!("svg: {} contours", contours.iter().map(Vec::len).sum::<usize>());
+}
+
+/// Calculate the escape iteration of every pixel of the render, continuous with `Smooth`
+/// coloring, over the same bands as the image
+fn render_field(
+    args: &RenderArguments, plotter: &(dyn PlottingAlgorithm + Send + Sync), coloring: Coloring,
+    upper_left: Complex<f64>, lower_right: Complex<f64>,
+) -> Vec<Option<f64>> {
+    let canvas = Canvas::new(plotter, coloring);
+    let mut values = vec![None; args.dimensions.0 * args.dimensions.1];
+    for_each_band(
+        &mut values,
+        args.dimensions,
+        upper_left,
+        lower_right,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            canvas.render_iterations(
                 band,
                 band_bounds,
                 band_upper_left,
                 band_lower_right,
                 args.limit,
-                args.invert,
             );
+        },
+    );
+    values
+}
+
+/// Work out the projection from the projection arguments; `None` for the default linear view,
+/// which renders by bands of the region
+fn render_projection(
+    args: &RenderArguments, zoom: f64, center: (f64, f64),
+) -> Result<Option<Box<dyn Projection + Sync>>, String> {
+    let spherical = matches!(args.projection.as_str(), "sphere" | "equirectangular");
+    if args.rotation != 0.0 && !(spherical || args.projection == "rotated") {
+        return Err(format!("--rotation doesn't apply to --projection {}", args.projection));
+    }
+    if args.pitch != 0.0 && !spherical {
+        return Err(format!("--pitch doesn't apply to --projection {}", args.projection));
+    }
+    if args.projection == "linear" {
+        return Ok(None);
+    }
+    if args.histogram || args.mesh.is_some() || args.svg.is_some() || !args.trace_ray.is_empty() {
+        return Err(format!(
+            "--projection {} can't be combined with --histogram, --mesh, --svg or --trace-ray",
+            args.projection
+        ));
+    }
+
+    let region = mandelbrot::calculate_region(zoom, center);
+    let sphere = projection::Sphere {
+        center: Complex::new(center.0, center.1),
+        radius: zoom / 2.0,
+        yaw: args.rotation.to_radians(),
+        pitch: args.pitch.to_radians(),
+    };
+    Ok(Some(match args.projection.as_str() {
+        "rotated" => Box::new(projection::Rotated { region, angle: args.rotation.to_radians() }),
+        "inversion" => Box::new(projection::Mobius::inversion(region)),
+        "mobius" => {
+            let [a, b, c, d] = args.mobius.ok_or("--mobius is required")?;
+            Box::new(projection::Mobius { region, a, b, c, d })
+        }
+        "sphere" => Box::new(projection::Stereographic(sphere)),
+        _ => Box::new(projection::Equirectangular(sphere)),
+    }))
+}
+
+/// Work out the coloring from the trap, decomposition and coloring arguments
+fn render_coloring(args: &RenderArguments) -> Result<Coloring, String> {
+    if !args.external_angle.is_empty() && args.coloring != "field-lines" {
+        return Err("--external-angle only applies to the field-lines coloring".to_string());
+    }
+    if let Some(trap) = &args.trap {
+        return Ok(Coloring::Trap { trap: trap.clone(), falloff: args.trap_falloff });
+    }
+
+    if args.coloring == "field-lines" && args.decomposition.is_none() {
+        // The potential and external angle are those of the Mandelbrot set
+        if args.algorithm != "escape_time" {
+            return Err("field line coloring only applies to the escape_time algorithm".to_string());
+        }
+        return Ok(Coloring::FieldLines {
+            density: args.field_line_density,
+            angles: args.external_angle.clone(),
         });
+    }
+
+    let decomposition = match (&args.decomposition, &args.decomposition_texture) {
+        (_, Some(path)) => Decomposition::Texture(coloring::Texture::open(path)?),
+        (Some(name), None) if name == "binary" => Decomposition::Binary,
+        (Some(_), None) => Decomposition::Angular,
+        (None, None) => return Ok(coloring::get_coloring(&args.coloring, args.stripe_density)),
+    };
+    if !["iterations", "smooth"].contains(&args.coloring.as_str()) {
+        return Err("decomposition only applies to the iterations and smooth colorings".to_string());
+    }
+    Ok(Coloring::Decomposition { decomposition, smooth: args.coloring == "smooth" })
+}
+
+/// Split `buffer` into rows and hand each one, along with its bounds and corners in the
+/// complex plane, to `render_band` on the rayon thread pool
+fn for_each_band<T: Send>(
+    buffer: &mut [T], bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>,
+    render_band: impl Fn(&mut [T], (usize, usize), Complex<f64>, Complex<f64>) + Sync,
+) {
+    let bands: Vec<(usize, &mut [T])> = buffer
+        .chunks_mut(bounds.0)
+        .enumerate()
+        .collect();
+
+    bands.into_par_iter().for_each(|(i, band)| {
+        let top = i;
+        let band_bounds = (bounds.0, 1);
+        let band_upper_left = mandelbrot::pixel_to_point(
+            bounds,
+            (0, top),
+            upper_left,
+            lower_right,
+        );
+        let band_lower_right = mandelbrot::pixel_to_point(
+            bounds,
+            (bounds.0, top + 1),
+            upper_left,
+            lower_right,
+        );
+
+        render_band(band, band_bounds, band_upper_left, band_lower_right);
+    });
+}
+
+/// Render in two passes: collect the iteration count of every pixel, then shade the counts
+/// through the histogram of the whole frame
+fn render_equalized(
+    args: &RenderArguments, canvas: &Canvas<impl PlottingAlgorithm + Sync>, pixels: &mut [u8],
+    upper_left: Complex<f64>, lower_right: Complex<f64>,
+) {
+    let mut values = vec![None; pixels.len()];
+    for_each_band(
+        &mut values,
+        args.dimensions,
+        upper_left,
+        lower_right,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            canvas.render_iterations(
+                band,
+                band_bounds,
+                band_upper_left,
+                band_lower_right,
+                args.limit,
+            );
+        },
+    );
+
+    let mut histogram = Histogram::new(&values, args.limit, args.exclude_interior);
+    if let Some(path) = &args.histogram_state {
+        // A missing state file is the first frame of an animation
+        if let Ok(state) = std::fs::read_to_string(path) {
+            match Histogram::from_state(&state) {
+                Ok(previous) => histogram.stabilize(&previous, args.histogram_momentum),
+                Err(err) => eprintln!("warning: ignoring histogram state {}: {}", path, err),
+            }
+        }
+        std::fs::write(path, histogram.to_state()).expect("error writing histogram state");
+    }
+
+    pixels.par_iter_mut().zip(values).for_each(|(pixel, value)| {
+        *pixel = match value {
+            None => 0,
+            Some(count) => {
+                let shade = histogram.shade(count, args.histogram_blend);
+                let shade = if args.invert { 1.0 - shade } else { shade };
+                (shade * u8::MAX as f64).round() as u8
+            }
+        };
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn sweep_arguments(flags: &[&str]) -> SweepArguments {
+        let args = ["mandelbrot", "sweep"].iter().chain(flags).copied();
+        match Arguments::try_parse_from(args).unwrap().command {
+            Some(Command::Sweep(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_sweep_exponent() {
+        let limits = ["-p", "limit", "--from", "10", "--to", "100"];
+        let args = sweep_arguments(&[&limits[..], &["--exponent", "3"]].concat());
+        assert!(sweep_parameter(&args).is_ok());
 
-        write_image(&args.output, &pixels, args.dimensions).expect("error writing PNG file");
+        // Only the escape time algorithm has an exponent to change
+        let args = sweep_arguments(&[&limits[..], &["-a", "phoenix", "--exponent", "3"]].concat());
+        assert!(sweep_parameter(&args).is_err());
+        let args = sweep_arguments(&[&limits[..], &["-a", "phoenix"]].concat());
+        assert!(sweep_parameter(&args).is_ok());
     }
 }
@@ -1,6 +1,12 @@
 use crate::algorithms::PlottingAlgorithm;
+use crate::coloring::{
+    Accumulator, Coloring, Decomposed, FieldLines, OrbitAverage, SmoothIterations,
+};
+use crate::projection::Projection;
+use crate::traps::TrapDistance;
 use crate::types::{Dimensions, Pixel};
 use num::complex::Complex;
+use std::f64::consts::TAU;
 
 /// Represents a rectangular region in the complex plane
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -30,6 +36,72 @@ impl ComplexRegion {
             im: self.upper_left.im - pixel.y as f64 * height / bounds.height as f64,
         }
     }
+
+    /// Convert a point in the complex plane to the pixel containing it, or `None` if the point
+    /// falls outside of this region
+    pub fn point_to_pixel(&self, bounds: Dimensions, point: Complex<f64>) -> Option<Pixel> {
+        let (x, y) = self.point_to_position(bounds, point);
+
+        if x >= 0.0 && y >= 0.0 && x < bounds.width as f64 && y < bounds.height as f64 {
+            Some(Pixel::new(x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Convert a point in the complex plane to continuous pixel coordinates, which may fall
+    /// outside of the image
+    pub fn point_to_position(&self, bounds: Dimensions, point: Complex<f64>) -> (f64, f64) {
+        let (width, height) = self.dimensions();
+        (
+            (point.re - self.upper_left.re) / width * bounds.width as f64,
+            (self.upper_left.im - point.im) / height * bounds.height as f64,
+        )
+    }
+}
+
+/// Maps the pixels of a strip in log-polar coordinates around `center`. Columns go once around
+/// the center, counterclockwise from the positive real axis, and rows go inwards from
+/// `outer_radius`, each shrinking the radius by the factor that keeps the pixels square. A strip
+/// a few thousand rows long covers dozens of octaves of zoom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialMap {
+    pub center: Complex<f64>,
+    pub outer_radius: f64,
+    pub width: usize,
+}
+
+impl ExponentialMap {
+    pub fn new(center: Complex<f64>, outer_radius: f64, width: usize) -> Self {
+        Self { center, outer_radius, width }
+    }
+
+    /// The angle between columns, which is also the step in log radius between rows
+    fn step(&self) -> f64 {
+        TAU / self.width as f64
+    }
+
+    /// The number of rows the strip needs to reach in to `inner_radius`
+    pub fn rows_to(&self, inner_radius: f64) -> usize {
+        ((self.outer_radius / inner_radius).ln() / self.step()).ceil() as usize + 1
+    }
+
+    /// Convert a pixel of the strip to a point in the complex plane
+    pub fn pixel_to_point(&self, pixel: Pixel) -> Complex<f64> {
+        let radius = self.outer_radius * (-(pixel.y as f64) * self.step()).exp();
+        self.center + Complex::from_polar(radius, pixel.x as f64 * self.step())
+    }
+
+    /// Convert a point in the complex plane to continuous coordinates in the strip, with x in
+    /// `[0, width)`. Points outside of the outer radius get a negative y, and the center an
+    /// infinite one.
+    pub fn point_to_position(&self, point: Complex<f64>) -> (f64, f64) {
+        let offset = point - self.center;
+        (
+            (offset.arg() / self.step()).rem_euclid(self.width as f64),
+            (self.outer_radius / offset.norm()).ln() / self.step(),
+        )
+    }
 }
 
 /// Calculate the corners of a square region in the complex plane
@@ -55,15 +127,19 @@ pub fn calculate_region(magnitude: f64, center: (f64, f64)) -> ComplexRegion {
     )
 }
 
-pub struct Canvas {
-    algorithm: Box<dyn PlottingAlgorithm + Send + Sync>,
+/// Draws an algorithm with a coloring. The algorithm's type is a parameter so the render loops
+/// are compiled for each formula; `Algorithm::dispatch` picks one at run time, and the default
+/// of a boxed `dyn PlottingAlgorithm` takes any other.
+pub struct Canvas<P = Box<dyn PlottingAlgorithm + Send + Sync>> {
+    algorithm: P,
+    coloring: Coloring,
     // height: usize,
     // width: usize,
     // zoom: usize,
 }
-impl Canvas {
-    pub fn new(algorithm: Box<dyn PlottingAlgorithm + Send + Sync>) -> Self {
-        Self { algorithm }
+impl<P: PlottingAlgorithm> Canvas<P> {
+    pub fn new(algorithm: P, coloring: Coloring) -> Self {
+        Self { algorithm, coloring }
     }
     pub fn render(
         &self, pixels: &mut [u8], bounds: (usize, usize), upper_left: Complex<f64>,
@@ -71,20 +147,106 @@ impl Canvas {
     ) {
         assert_eq!(pixels.len(), bounds.0 * bounds.1);
 
-        for row in 0..bounds.1 {
-            for column in 0..bounds.0 {
-                let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
-                pixels[row * bounds.0 + column] = match self.algorithm.calculate(point, 1, limit) {
+        // Plain iteration counts are calculated a row at a time, so the algorithm can batch them
+        let mut points = Vec::with_capacity(bounds.0);
+        let mut counts = vec![None; bounds.0];
+        for (row, line) in pixels.chunks_mut(bounds.0.max(1)).enumerate() {
+            let row_points = row_points(bounds, row, upper_left, lower_right);
+            if let Coloring::Iterations = self.coloring {
+                points.clear();
+                points.extend(row_points);
+                self.algorithm.calculate_batch(&points, limit, &mut counts);
+                for (pixel, count) in line.iter_mut().zip(&counts) {
+                    *pixel = gray(*count, limit, invert);
+                }
+            } else {
+                for (pixel, point) in line.iter_mut().zip(row_points) {
+                    *pixel = self.color(point, limit, invert);
+                }
+            }
+        }
+    }
+
+    /// Render the rows of an image of `bounds` from `first_row` on into `pixels`, mapping them
+    /// to the plane through `projection`. Pixels that show no point of the plane are black.
+    pub fn render_projected(
+        &self, pixels: &mut [u8], bounds: Dimensions, first_row: usize,
+        projection: &dyn Projection, limit: usize, invert: bool,
+    ) {
+        assert_eq!(pixels.len() % bounds.width, 0);
+
+        for (row, line) in pixels.chunks_mut(bounds.width).enumerate() {
+            for (column, pixel) in line.iter_mut().enumerate() {
+                *pixel = match projection.pixel_to_point(bounds, Pixel::new(column, first_row + row))
+                {
+                    Some(point) => self.color(point, limit, invert),
                     None => 0,
-                    Some(count) => map_ranges(
-                        if invert { limit - count } else { count },
-                        (0, limit),
-                        (0, u8::MAX.into()),
-                    ) as u8,
                 };
             }
         }
     }
+
+    /// Calculate the escape iteration of every pixel, continuous if the coloring is `Smooth`.
+    /// Pixels whose orbits never escape are `None`.
+    pub fn render_iterations(
+        &self, values: &mut [Option<f64>], bounds: (usize, usize), upper_left: Complex<f64>,
+        lower_right: Complex<f64>, limit: usize,
+    ) {
+        assert_eq!(values.len(), bounds.0 * bounds.1);
+
+        for (row, line) in values.chunks_mut(bounds.0.max(1)).enumerate() {
+            for (value, point) in
+                line.iter_mut().zip(row_points(bounds, row, upper_left, lower_right))
+            {
+                *value = self.iterations(point, limit);
+            }
+        }
+    }
+
+    fn iterations(&self, c: Complex<f64>, limit: usize) -> Option<f64> {
+        match &self.coloring {
+            Coloring::Smooth => {
+                let mut smooth = SmoothIterations::new(limit);
+                let escape = self.algorithm.orbit(c, limit, &mut smooth);
+                smooth.count(escape, self.algorithm.escape_radius())
+            }
+            _ => self.algorithm.calculate(c, 1, limit).map(|count| count as f64),
+        }
+    }
+
+    /// Calculate the gray level of the point `c`
+    fn color(&self, c: Complex<f64>, limit: usize, invert: bool) -> u8 {
+        match &self.coloring {
+            Coloring::Iterations => gray(self.algorithm.calculate(c, 1, limit), limit, invert),
+            Coloring::Smooth => self.shade(c, limit, invert, SmoothIterations::new(limit)),
+            Coloring::Trap { trap, falloff } => {
+                self.shade(c, limit, invert, TrapDistance::new(trap, *falloff))
+            }
+            Coloring::Average(statistic) => {
+                self.shade(c, limit, invert, OrbitAverage::new(*statistic, c))
+            }
+            Coloring::Decomposition { decomposition, smooth } => {
+                self.shade(c, limit, invert, Decomposed::new(decomposition, limit, *smooth))
+            }
+            Coloring::FieldLines { density, angles } => {
+                self.shade(c, limit, invert, FieldLines::new(*density, angles, limit))
+            }
+        }
+    }
+
+    /// Feed the orbit of `c` to `accumulator` and convert the resulting shade to a gray level
+    fn shade<A: Accumulator>(
+        &self, c: Complex<f64>, limit: usize, invert: bool, mut accumulator: A,
+    ) -> u8 {
+        let escape = self.algorithm.orbit(c, limit, &mut accumulator);
+        match accumulator.shade(escape, self.algorithm.escape_radius()) {
+            None => 0,
+            Some(shade) => {
+                let shade = if invert { 1.0 - shade } else { shade };
+                (shade * u8::MAX as f64).round() as u8
+            }
+        }
+    }
 }
 
 /// Convert the given pixel to a point on the complex plane. The resulting point is the intersection of the line through
@@ -114,9 +276,25 @@ pub fn pixel_to_point(
     }
 }
 
+/// The points of the pixels of `row`, as `pixel_to_point` gives them, stepping across from the
+/// left edge instead of working each one out afresh. What is stepped is the offset from
+/// `upper_left`, which is only added at the end, so however wide the image the points are
+/// rounded as finely as the corner allows rather than drifting by a rounding per pixel.
+pub fn row_points(
+    bounds: (usize, usize), row: usize, upper_left: Complex<f64>, lower_right: Complex<f64>,
+) -> impl Iterator<Item = Complex<f64>> {
+    let step = (lower_right.re - upper_left.re) / bounds.0 as f64;
+    let im = pixel_to_point(bounds, (0, row), upper_left, lower_right).im;
+    (0..bounds.0).scan(0.0, move |offset, _| {
+        let re = upper_left.re + *offset;
+        *offset += step;
+        Some(Complex::new(re, im))
+    })
+}
+
 /// Calculate the corners of a square in the complex plane.
 ///
-/// Given a magnitude and center point, finds the bottom-left and top-right corners of
+/// Given a magnitude and center point, finds the upper-left and lower-right corners of
 /// containing square in the complex plain. The corners are returned as two Complex numbers
 ///
 /// # Arguments
@@ -126,19 +304,19 @@ pub fn pixel_to_point(
 ///
 /// # Returns
 ///
-/// Returns a tuple `(bottom_left, top_right)` representing the bottom-left and top-right corners
-/// respectively. Both corners are represented as a `Complex<f64>` struct, which has fields `re`
-/// (real component) and `im` (imaginary component).
+/// Returns a tuple `(upper_left, lower_right)` representing the upper-left and lower-right
+/// corners respectively. Both corners are represented as a `Complex<f64>` struct, which has
+/// fields `re` (real component) and `im` (imaginary component).
 ///
 /// # Example
 /// ```
 /// use num::complex::Complex;
-/// use my_math_lib::calculate_corners;
+/// use mandelbrot::mandelbrot::calculate_corners;
 /// let mag = 4.0_f64;
 /// let center = (-2.0_f64, 3.0_f64);
-/// let (bottom_left, top_right) = calculate_corners(mag, center);
-/// assert_eq!(bottom_left, Complex::new(-4.0, 1.0));
-/// assert_eq!(top_right, Complex::new(0.0, 5.0));
+/// let (upper_left, lower_right) = calculate_corners(mag, center);
+/// assert_eq!(upper_left, Complex::new(-4.0, 5.0));
+/// assert_eq!(lower_right, Complex::new(0.0, 1.0));
 /// ```
 pub fn calculate_corners(magnitude: f64, center: (f64, f64)) -> (Complex<f64>, Complex<f64>) {
     let half_mag = magnitude / 2.0;
@@ -149,6 +327,18 @@ pub fn calculate_corners(magnitude: f64, center: (f64, f64)) -> (Complex<f64>, C
     )
 }
 
+/// The gray level of an escape after `count` iterations, black for orbits that never escape
+fn gray(count: Option<usize>, limit: usize, invert: bool) -> u8 {
+    match count {
+        None => 0,
+        Some(count) => map_ranges(
+            if invert { limit - count } else { count },
+            (0, limit),
+            (0, u8::MAX.into()),
+        ) as u8,
+    }
+}
+
 /// Map value in range to cooresponding value in another range
 fn map_ranges(value: usize, from: (usize, usize), to: (usize, usize)) -> usize {
     let range = from.1 - from.0;
@@ -160,7 +350,16 @@ fn map_ranges(value: usize, from: (usize, usize), to: (usize, usize)) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::algorithms::EscapeTime;
+    use crate::algorithms::{
+        Algorithm, Bailout, Biomorph, BurningShip, Collatz, Escape, EscapeTime, Fold, Hybrid, Julia,
+        Lambda, Magnet, MagnetType, Parameters, Phoenix, Tetration, Transcendental,
+        TranscendentalMap,
+    };
+
+    /// The escape time algorithm escaping at `|z| > 2`, which the counts below were worked out for
+    fn radius_two() -> EscapeTime {
+        EscapeTime { escape: Escape::default() }
+    }
 
     #[test]
     fn test_pixel_to_point_upper_left() {
@@ -201,6 +400,28 @@ mod tests {
         assert_eq!(result.re, 0.0);
     }
 
+    #[test]
+    fn test_exponential_map() {
+        let map = ExponentialMap::new(Complex::new(-0.5, 0.25), 2.0, 100);
+        // The first row is the outer circle, and a quarter of the way across is straight up
+        assert!((map.pixel_to_point(Pixel::new(25, 0)) - Complex::new(-0.5, 2.25)).norm() < 1e-12);
+
+        // Rows shrink the radius so that pixels stay square: the arc between columns matches
+        // the step between rows, to first order
+        let (a, b) = (map.pixel_to_point(Pixel::new(0, 40)), map.pixel_to_point(Pixel::new(1, 40)));
+        let below = map.pixel_to_point(Pixel::new(0, 41));
+        assert!(((a - b).norm() / (a - below).norm() - 1.0).abs() < 0.05);
+
+        for pixel in [Pixel::new(0, 0), Pixel::new(13, 7), Pixel::new(99, 250)] {
+            let (x, y) = map.point_to_position(map.pixel_to_point(pixel));
+            assert!((x - pixel.x as f64).abs() < 1e-6 && (y - pixel.y as f64).abs() < 1e-6);
+        }
+
+        // Each halving of the radius takes 100 ln 2 / 2π ≈ 11 rows
+        assert_eq!(map.rows_to(1.0), 13);
+        assert_eq!(map.rows_to(2.0 / 1024.0), 112);
+    }
+
     #[test]
     fn test_calculate_corners() {
         //within the reasonable range
@@ -227,31 +448,330 @@ mod tests {
     #[test]
     fn test_escape_time_zero() {
         let c = Complex::new(0.0, 0.0);
-        assert_eq!(EscapeTime.calculate(c, 100, 10), None);
+        assert_eq!(radius_two().calculate(c, 100, 10), None);
     }
 
     #[test]
     fn test_escape_time_constant_c() {
         let c = Complex::new(1.0, 2.0);
-        assert_eq!(EscapeTime.calculate(c, 0, 100), Some(1));
+        assert_eq!(radius_two().calculate(c, 0, 100), Some(1));
     }
 
     #[test]
     fn test_escape_time_periodic_c() {
         let c = Complex::new(-0.4, 0.6);
-        assert_eq!(EscapeTime.calculate(c, 0, 1000), Some(26));
+        assert_eq!(radius_two().calculate(c, 0, 1000), Some(26));
     }
 
     #[test]
     fn test_escape_time_outside_main_cardioid() {
         let c = Complex::new(-1.75, -0.02);
-        assert_eq!(EscapeTime.calculate(c, 0, 1000), Some(13));
+        assert_eq!(radius_two().calculate(c, 0, 1000), Some(13));
     }
 
     #[test]
     fn test_escape_time_outside_period_2_bulb() {
         let c = Complex::new(0.32, -0.04);
-        assert_eq!(EscapeTime.calculate(c, 0, 1000), None);
+        assert_eq!(radius_two().calculate(c, 0, 1000), None);
+    }
+
+    fn fold(fold: Fold) -> BurningShip {
+        BurningShip { fold, escape: Escape::default() }
+    }
+
+    #[test]
+    fn test_burning_ship() {
+        let ship = fold(Fold::BurningShip);
+        assert_eq!(ship.calculate(Complex::new(-1.75, -0.03), 0, 1000), Some(22));
+        assert_eq!(ship.calculate(Complex::new(0.3, 0.5), 0, 1000), Some(8));
+        assert_eq!(ship.calculate(Complex::new(0.2, -0.9), 0, 1000), None);
+
+        // Folding breaks the symmetry about the real axis
+        assert_eq!(ship.calculate(Complex::new(-0.2, 0.7), 0, 1000), Some(3));
+        assert_eq!(ship.calculate(Complex::new(-0.2, -0.7), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_celtic() {
+        let celtic = fold(Fold::Celtic);
+        assert_eq!(celtic.calculate(Complex::new(-1.75, -0.03), 0, 1000), Some(17));
+        assert_eq!(celtic.calculate(Complex::new(-1.0, 0.3), 0, 1000), Some(10));
+        assert_eq!(celtic.calculate(Complex::new(-0.5, -0.5), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_perpendicular_burning_ship() {
+        let perpendicular = fold(Fold::PerpendicularBurningShip);
+        assert_eq!(perpendicular.calculate(Complex::new(-1.75, -0.03), 0, 1000), Some(11));
+        assert_eq!(perpendicular.calculate(Complex::new(0.4, 0.4), 0, 1000), Some(9));
+        assert_eq!(perpendicular.calculate(Complex::new(0.3, 0.5), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_buffalo() {
+        let buffalo = fold(Fold::Buffalo);
+        assert_eq!(buffalo.calculate(Complex::new(-1.75, -0.03), 0, 1000), Some(79));
+        assert_eq!(buffalo.calculate(Complex::new(-1.0, 0.3), 0, 1000), Some(9));
+        assert_eq!(buffalo.calculate(Complex::new(0.2, -0.9), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_mandelbar_celtic() {
+        let mandelbar = fold(Fold::MandelbarCeltic);
+        assert_eq!(mandelbar.calculate(Complex::new(-1.0, 0.3), 0, 1000), Some(29));
+        assert_eq!(mandelbar.calculate(Complex::new(-0.5, -0.5), 0, 1000), Some(5));
+        assert_eq!(mandelbar.calculate(Complex::new(-0.2, 0.7), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_phoenix() {
+        let phoenix = Phoenix { p: Complex::new(-0.5, 0.0), escape: Escape::default() };
+        let c = Complex::new(0.25, 0.5);
+        let mut visited = Vec::new();
+        phoenix.orbit(c, 3, &mut |z| visited.push(z));
+        assert_eq!(visited[1], c * c + c);
+        assert_eq!(visited[2], visited[1] * visited[1] + c - 0.5 * c);
+
+        assert_eq!(phoenix.calculate(Complex::new(0.5, 0.5), 0, 1000), Some(5));
+        assert_eq!(phoenix.calculate(Complex::new(-1.2, 0.0), 0, 1000), None);
+
+        // Without the previous point it is the escape time algorithm
+        let phoenix = Phoenix { p: Complex::new(0.0, 0.0), escape: Escape::default() };
+        let c = Complex::new(-0.4, 0.6);
+        assert_eq!(phoenix.calculate(c, 0, 1000), radius_two().calculate(c, 0, 1000));
+    }
+
+    #[test]
+    fn test_magnet() {
+        let magnet = Magnet { kind: MagnetType::One, escape: Escape::default() };
+        // Far out, orbits fall onto the fixed point at 1
+        assert_eq!(magnet.calculate(Complex::new(10.0, 0.0), 0, 1000), Some(4));
+        assert_eq!(magnet.calculate(Complex::new(0.5, 0.0), 0, 1000), None);
+        // The first step divides by zero
+        assert_eq!(magnet.calculate(Complex::new(2.0, 0.0), 0, 1000), Some(1));
+        assert_eq!(magnet.escape_radius(), 100.0);
+
+        let magnet = Magnet { kind: MagnetType::Two, escape: Escape::default() };
+        assert_eq!(magnet.calculate(Complex::new(10.0, 0.0), 0, 1000), Some(2));
+        assert_eq!(magnet.calculate(Complex::new(0.5, 0.0), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_lambda() {
+        let lambda = Lambda::default();
+        // At λ = 2 the critical point 1/2 is fixed
+        assert_eq!(lambda.calculate(Complex::new(2.0, 0.0), 0, 1000), None);
+        assert_eq!(lambda.calculate(Complex::new(4.5, 0.0), 0, 1000), Some(3));
+
+        // For small λ, points past the escape radius can still fall back to 0
+        let (z0, c) = (Complex::new(3.0, 0.0), Complex::new(0.3, 0.0));
+        let mut visited = Vec::new();
+        assert_eq!(lambda.orbit_from(z0, c, 1000, &mut |z| visited.push(z)), None);
+        assert_eq!(visited[0], Complex::new(0.3 * 3.0 * -2.0, 0.0));
+    }
+
+    fn transcendental(function: Transcendental) -> TranscendentalMap {
+        TranscendentalMap { function, escape: Escape::default() }
+    }
+
+    #[test]
+    fn test_exponential() {
+        let exponential = transcendental(Transcendental::Exponential);
+        // λe^z has an attracting fixed point for real λ below 1/e
+        assert_eq!(exponential.calculate(Complex::new(0.3, 0.0), 0, 1000), None);
+        assert_eq!(exponential.calculate(Complex::new(0.5, 0.0), 0, 1000), Some(7));
+
+        // Only the real part is tested, and only to the right
+        let (left, far_up, c) =
+            (Complex::new(-100.0, 0.0), Complex::new(0.0, 100.0), Complex::new(1.0, 0.0));
+        let mut visited = Vec::new();
+        let escape = exponential.orbit_from(left, c, 1, &mut |z| visited.push(z));
+        assert_eq!(escape, None);
+        assert_eq!(visited[0], Complex::new((-100.0f64).exp(), 0.0));
+        assert_eq!(exponential.orbit_from(far_up, Complex::new(0.1, 0.0), 1, &mut |_| {}), None);
+        assert_eq!(exponential.orbit_from(-left, Complex::new(0.1, 0.0), 1, &mut |_| {}), Some(0));
+    }
+
+    #[test]
+    fn test_sine_and_cosine() {
+        // The orbit of c sin z starts from the critical point π/2, so it first lands on c
+        let sine = transcendental(Transcendental::Sine);
+        let c = Complex::new(0.7, 0.2);
+        let mut visited = Vec::new();
+        sine.orbit(c, 1, &mut |z| visited.push(z));
+        assert!((visited[0] - c).norm() < 1e-15);
+        assert_eq!(sine.calculate(Complex::new(1.0, 0.0), 0, 1000), None);
+        assert_eq!(sine.calculate(Complex::new(1.0, 1.0), 0, 1000), Some(5));
+
+        let cosine = transcendental(Transcendental::Cosine);
+        assert_eq!(cosine.calculate(Complex::new(0.5, 0.0), 0, 1000), None);
+        assert_eq!(cosine.calculate(Complex::new(0.0, 2.0), 0, 1000), Some(3));
+        assert_eq!(cosine.escape_radius(), 50.0);
+    }
+
+    #[test]
+    fn test_biomorph() {
+        let biomorph =
+            Julia { algorithm: Box::new(Biomorph::default()), c: Complex::new(0.5, 0.0) };
+        assert_eq!(biomorph.calculate(Complex::new(0.0, 0.0), 0, 1000), None);
+        // Both components end up outside the threshold
+        assert_eq!(biomorph.calculate(Complex::new(3.0, 3.0), 0, 1000), Some(1));
+        // The orbit stops with the imaginary part still small, so it is part of the body
+        assert_eq!(biomorph.calculate(Complex::new(3.0, 0.1), 0, 1000), None);
+        assert_eq!(biomorph.calculate(Complex::new(20.0, 0.0), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_hybrid() {
+        // A single formula is the same as the formula itself
+        let mandelbrot = Hybrid::parse("M", Escape::default()).unwrap();
+        let c = Complex::new(-0.4, 0.6);
+        assert_eq!(mandelbrot.calculate(c, 0, 1000), radius_two().calculate(c, 0, 1000));
+        let ship = Hybrid::parse("bb", Escape::default()).unwrap();
+        let c = Complex::new(-1.75, -0.03);
+        assert_eq!(ship.calculate(c, 0, 1000), fold(Fold::BurningShip).calculate(c, 0, 1000));
+
+        // Steps alternate, starting from the first letter
+        let hybrid = Hybrid::parse("MB", Escape::default()).unwrap();
+        let c = Complex::new(-0.3, 0.4);
+        let mut visited = Vec::new();
+        hybrid.orbit(c, 3, &mut |z| visited.push(z));
+        let z2 = Complex::new(c.re * c.re - c.im * c.im, 2.0 * (c.re * c.im).abs()) + c;
+        assert_eq!(visited, vec![c, z2, z2 * z2 + c]);
+
+        assert!(Hybrid::parse("", Escape::default()).is_err());
+        assert!(Hybrid::parse("MX", Escape::default()).is_err());
+        let parameters = Parameters { hybrid: "MX".to_string(), ..Parameters::default() };
+        assert!(Algorithm::new("hybrid", Escape::default(), &parameters).is_err());
+    }
+
+    #[test]
+    fn test_collatz() {
+        let collatz = Collatz::default();
+        // Integers follow the Collatz sequence: 3, 10, 5, 16, 8, 4, 2, 1, 4, ...
+        let mut visited = Vec::new();
+        assert_eq!(collatz.orbit(Complex::new(3.0, 0.0), 1000, &mut |z| visited.push(z)), None);
+        let expected = [10.0, 5.0, 16.0, 8.0, 4.0, 2.0, 1.0, 4.0];
+        assert!(visited.iter().zip(expected).all(|(z, n)| (z - n).norm() < 1e-9));
+        // The cycle is caught well before the limit
+        assert!(visited.len() < 20);
+
+        assert_eq!(collatz.calculate(Complex::new(0.5, 2.0), 0, 1000), Some(1));
+        assert_eq!(collatz.calculate(Complex::new(-0.2, 0.1), 0, 1000), None);
+    }
+
+    #[test]
+    fn test_tetration() {
+        let tetration = Tetration::default();
+        // √2^√2^√2... converges to 2
+        let mut last = Complex::new(0.0, 0.0);
+        assert_eq!(tetration.orbit(Complex::new(2f64.sqrt(), 0.0), 1000, &mut |z| last = z), None);
+        assert!((last - 2.0).norm() < 1e-6);
+
+        // Towers of 2 overflow
+        assert_eq!(tetration.calculate(Complex::new(2.0, 0.0), 0, 1000), Some(5));
+        // (-1)^1 = -1 is a fixed point
+        let mut steps = 0;
+        assert_eq!(tetration.orbit(Complex::new(-1.0, 0.0), 1000, &mut |_| steps += 1), None);
+        assert!(steps < 5);
+    }
+
+    #[test]
+    fn test_orbit_matches_calculate() {
+        let c = Complex::new(-0.4, 0.6);
+        let mut visited = Vec::new();
+        let result = EscapeTime::default().orbit(c, 1000, &mut |z| visited.push(z));
+
+        assert_eq!(result, EscapeTime::default().calculate(c, 0, 1000));
+        assert_eq!(Some(visited.len()), result);
+        assert_eq!(visited[0], c);
+        assert_eq!(visited[1], c * c + c);
+    }
+
+    #[test]
+    fn test_bailout_shapes() {
+        let z = Complex::new(1.5, -1.5);
+        let escaped = |bailout| Escape::new(2.0, bailout).escaped(z);
+        assert!(escaped(Bailout::Circle));
+        assert!(!escaped(Bailout::Square));
+        assert!(!escaped(Bailout::Real));
+        assert!(!escaped(Bailout::Imaginary));
+        assert!(escaped(Bailout::Manhattan));
+
+        // Overflowing orbits escape whatever the shape
+        assert!(Escape::new(2.0, Bailout::Real).escaped(Complex::new(0.0, f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_escape_radius() {
+        let c = Complex::new(0.5, 0.5);
+        let count = |radius| {
+            let algorithm = EscapeTime { escape: Escape::new(radius, Bailout::Circle) };
+            let mut smooth = SmoothIterations::new(1000);
+            let escape = algorithm.orbit(c, 1000, &mut smooth);
+            smooth.count(escape, algorithm.escape_radius()).unwrap()
+        };
+
+        // Squaring the radius takes one more iteration, and smoothing keeps that exact
+        assert!((count(100.0) - count(10.0) - 1.0).abs() < 1e-3);
+        assert!((count(1e6) - count(1e3) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_trap_coloring() {
+        let trap = crate::traps::OrbitTrap::parse("point:0,0").unwrap();
+        let canvas = Canvas::new(Box::new(EscapeTime::default()), Coloring::Trap { trap, falloff: 0.1 });
+
+        // The orbit of 0 never leaves the trap, while a distant point escapes before nearing it
+        assert_eq!(canvas.color(Complex::new(0.0, 0.0), 10, false), u8::MAX);
+        assert_eq!(canvas.color(Complex::new(0.0, 0.0), 10, true), 0);
+        assert_eq!(canvas.color(Complex::new(10.0, 0.0), 10, false), 0);
+    }
+
+    #[test]
+    fn test_render_batches_match_color() {
+        // Rows of iteration counts go through the lane kernel, with a remainder past the lanes
+        let canvas = Canvas::new(Box::new(EscapeTime::default()), Coloring::Iterations);
+        let bounds = (27, 19);
+        let (upper_left, lower_right) = (Complex::new(-2.2, 1.2), Complex::new(0.8, -1.2));
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        canvas.render(&mut pixels, bounds, upper_left, lower_right, 300, true);
+
+        // Stepping across the rows can land an ulp away from `pixel_to_point`, which may tip a
+        // pixel on the boundary over to the next count; those few must match the stepped point
+        let mut differing = 0;
+        for (i, pixel) in pixels.iter().enumerate() {
+            let pixel_index = (i % bounds.0, i / bounds.0);
+            let point = pixel_to_point(bounds, pixel_index, upper_left, lower_right);
+            if *pixel != canvas.color(point, 300, true) {
+                differing += 1;
+                let mut row = row_points(bounds, pixel_index.1, upper_left, lower_right);
+                let stepped = row.nth(pixel_index.0).unwrap();
+                assert!((stepped - point).norm() < 1e-12);
+                assert_eq!(*pixel, canvas.color(stepped, 300, true));
+            }
+        }
+        assert!(differing <= pixels.len() / 100);
+    }
+
+    #[test]
+    fn test_row_points() {
+        let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+        let points: Vec<_> = row_points((4, 2), 1, upper_left, lower_right).collect();
+        let expected: Vec<_> =
+            (0..4).map(|x| pixel_to_point((4, 2), (x, 1), upper_left, lower_right)).collect();
+        assert_eq!(points, expected);
+
+        // Deep in, across a wide image, the stepped points stay a tiny fraction of a pixel from
+        // the ones worked out afresh
+        let bounds = (8000, 1);
+        let upper_left = Complex::new(-0.743643887037151, 0.131825904205330);
+        let lower_right = upper_left + Complex::new(1e-9, -1e-9);
+        let pixel = 1e-9 / bounds.0 as f64;
+        for (x, point) in row_points(bounds, 0, upper_left, lower_right).enumerate() {
+            let expected = pixel_to_point(bounds, (x, 0), upper_left, lower_right);
+            assert!((point.re - expected.re).abs() < pixel * 1e-2, "pixel {}", x);
+        }
     }
 
     #[test]
@@ -346,4 +866,18 @@ mod tests {
         assert_eq!(point.re, 0.98);
         assert_eq!(point.im, -0.99);
     }
+
+    #[test]
+    fn test_point_to_pixel_complex_region() {
+        let bounds = Dimensions::new(100, 200);
+        let region = ComplexRegion::new(Complex::new(-1.0, 1.0), Complex::new(1.0, -1.0));
+
+        assert_eq!(region.point_to_pixel(bounds, Complex::new(-1.0, 1.0)), Some(Pixel::new(0, 0)));
+        assert_eq!(region.point_to_pixel(bounds, Complex::new(0.0, 0.0)), Some(Pixel::new(50, 100)));
+        assert_eq!(region.point_to_pixel(bounds, Complex::new(0.99, -0.999)), Some(Pixel::new(99, 199)));
+
+        // Points on or beyond the lower right edge are outside the image
+        assert_eq!(region.point_to_pixel(bounds, Complex::new(1.0, 0.0)), None);
+        assert_eq!(region.point_to_pixel(bounds, Complex::new(0.0, 1.5)), None);
+    }
 }
@@ -0,0 +1,427 @@
+use crate::types::Dimensions;
+use std::f64::consts::LN_2;
+use std::io::{self, Write};
+
+/// Which field of a render a relief is raised from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    /// The continuous escape time, which rises in steps of equal height towards the set
+    Smooth,
+    /// The estimated distance to the set, on a log scale, which rises steeply close to the set
+    /// and leaves the far exterior flat
+    Distance,
+}
+
+impl Field {
+    /// Turn the continuous escape times of a render, as produced by `Canvas::render_iterations`
+    /// with smooth coloring, into elevations: higher closer to the set, `None` inside it
+    pub fn elevations(&self, values: &[Option<f64>], bounds: Dimensions) -> Vec<Option<f64>> {
+        match self {
+            Field::Smooth => values.to_vec(),
+            Field::Distance => distance_field(values, bounds)
+                .into_iter()
+                .map(|distance| distance.map(|d| -d.ln()))
+                .collect(),
+        }
+    }
+}
+
+/// Estimate the distance from each pixel to the set, in pixels, from the continuous escape times
+/// `values`. Pixels inside the set are `None`.
+///
+/// For quadratic formulas `2^-ν` approximates the potential `G`, and `G / |∇G|` the distance to
+/// the set, which comes to `1 / (ln 2 |∇ν|)`. The gradient is taken by differences with the
+/// neighboring pixels, one-sided next to the edges of the image and the interior.
+pub fn distance_field(values: &[Option<f64>], bounds: Dimensions) -> Vec<Option<f64>> {
+    assert_eq!(values.len(), bounds.total_pixels());
+
+    let at = |x: usize, y: usize| values[y * bounds.width + x];
+    // The derivative along one axis, from the neighbors at `before` and `after` where present
+    let derivative = |value: f64, before: Option<f64>, after: Option<f64>| match (before, after) {
+        (Some(before), Some(after)) => (after - before) / 2.0,
+        (Some(before), None) => value - before,
+        (None, Some(after)) => after - value,
+        (None, None) => 0.0,
+    };
+
+    (0..bounds.total_pixels())
+        .map(|index| {
+            let (x, y) = (index % bounds.width, index / bounds.width);
+            let value = values[index]?;
+            let dx = derivative(
+                value,
+                x.checked_sub(1).and_then(|x| at(x, y)),
+                (x + 1 < bounds.width).then(|| at(x + 1, y)).flatten(),
+            );
+            let dy = derivative(
+                value,
+                y.checked_sub(1).and_then(|y| at(x, y)),
+                (y + 1 < bounds.height).then(|| at(x, y + 1)).flatten(),
+            );
+            Some(1.0 / (LN_2 * dx.hypot(dy)))
+        })
+        .collect()
+}
+
+/// The height of every pixel of a render, in pixel widths
+pub struct Heightmap {
+    bounds: Dimensions,
+    heights: Vec<f64>,
+}
+
+impl Heightmap {
+    /// Scale `elevations` to heights between 0 and `scale`, after clamping them to `clamp`, or
+    /// to the range of the finite elevations if it is `None`. Pixels inside the set, and any
+    /// beyond the top of the range, sit at the top.
+    pub fn new(
+        elevations: &[Option<f64>], bounds: Dimensions, scale: f64, clamp: Option<(f64, f64)>,
+    ) -> Self {
+        assert_eq!(elevations.len(), bounds.total_pixels());
+
+        let (low, high) = clamp.unwrap_or_else(|| {
+            elevations
+                .iter()
+                .flatten()
+                .filter(|e| e.is_finite())
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &e| {
+                    (low.min(e), high.max(e))
+                })
+        });
+        let heights = elevations
+            .iter()
+            .map(|elevation| match elevation {
+                _ if high <= low => 0.0,
+                None => scale,
+                Some(e) => (e.clamp(low, high) - low) / (high - low) * scale,
+            })
+            .collect();
+
+        Self { bounds, heights }
+    }
+
+    fn height(&self, x: usize, y: usize) -> f64 {
+        self.heights[y * self.bounds.width + x]
+    }
+
+    /// Triangulate the heightmap, a vertex per pixel, with the top row of the image furthest
+    /// along y. With `decimate`, flat stretches (such as the interior of the set) are covered by
+    /// a few large triangles rather than two per pixel. With a `base` thickness, walls and a
+    /// floor close the relief into a solid, as 3D printing needs.
+    pub fn mesh(&self, decimate: bool, base: Option<f64>) -> Mesh {
+        let (width, height) = (self.bounds.width, self.bounds.height);
+        assert!(width >= 2 && height >= 2, "a heightmap mesh needs at least 2x2 pixels");
+
+        let mut mesh = Mesh::default();
+        for y in 0..height {
+            for x in 0..width {
+                mesh.vertices.push([x as f64, (height - 1 - y) as f64, self.height(x, y)]);
+            }
+        }
+
+        let cells = (0, 0, width - 1, height - 1);
+        if decimate {
+            self.cover(&mut mesh, cells);
+        } else {
+            for y in 0..height - 1 {
+                for x in 0..width - 1 {
+                    self.cover_cell(&mut mesh, x, y);
+                }
+            }
+        }
+
+        if let Some(thickness) = base {
+            self.close(&mut mesh, thickness);
+        }
+        mesh
+    }
+
+    fn vertex(&self, x: usize, y: usize) -> usize {
+        y * self.bounds.width + x
+    }
+
+    /// Cover the cells between the vertices `x0..=x1` and `y0..=y1`, splitting them in halves
+    /// until each part is flat or a single cell. A fan takes two triangles per cell along the
+    /// edge of the rectangle, so it is only used where that is fewer than two per cell.
+    fn cover(&self, mesh: &mut Mesh, (x0, y0, x1, y1): (usize, usize, usize, usize)) {
+        let (columns, rows) = (x1 - x0, y1 - y0);
+        if columns * rows > columns + rows && self.is_flat(x0, y0, x1, y1) {
+            self.cover_flat(mesh, x0, y0, x1, y1);
+        } else if columns == 1 && rows == 1 {
+            self.cover_cell(mesh, x0, y0);
+        } else if columns >= rows {
+            let middle = (x0 + x1) / 2;
+            self.cover(mesh, (x0, y0, middle, y1));
+            self.cover(mesh, (middle, y0, x1, y1));
+        } else {
+            let middle = (y0 + y1) / 2;
+            self.cover(mesh, (x0, y0, x1, middle));
+            self.cover(mesh, (x0, middle, x1, y1));
+        }
+    }
+
+    fn is_flat(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> bool {
+        let level = self.height(x0, y0);
+        (y0..=y1).all(|y| (x0..=x1).all(|x| self.height(x, y) == level))
+    }
+
+    /// Two triangles over the cell with its upper left corner at `(x, y)`, counterclockwise
+    /// seen from above
+    fn cover_cell(&self, mesh: &mut Mesh, x: usize, y: usize) {
+        let upper_left = self.vertex(x, y);
+        let upper_right = self.vertex(x + 1, y);
+        let lower_left = self.vertex(x, y + 1);
+        let lower_right = self.vertex(x + 1, y + 1);
+        mesh.triangles.push([lower_left, lower_right, upper_right]);
+        mesh.triangles.push([lower_left, upper_right, upper_left]);
+    }
+
+    /// A fan around a new vertex in the middle of a flat rectangle. The fan takes in every vertex
+    /// on the rectangle's edges, so that it meets the triangles around it without cracks.
+    fn cover_flat(&self, mesh: &mut Mesh, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let height = self.bounds.height;
+        let center = mesh.vertices.len();
+        mesh.vertices.push([
+            (x0 + x1) as f64 / 2.0,
+            (height - 1) as f64 - (y0 + y1) as f64 / 2.0,
+            self.height(x0, y0),
+        ]);
+
+        let edge = rectangle_edge(x0, y0, x1, y1);
+        for (i, &(x, y)) in edge.iter().enumerate() {
+            let (next_x, next_y) = edge[(i + 1) % edge.len()];
+            mesh.triangles.push([center, self.vertex(x, y), self.vertex(next_x, next_y)]);
+        }
+    }
+
+    /// Add walls down from the edge of the relief to a floor `thickness` below height 0
+    fn close(&self, mesh: &mut Mesh, thickness: f64) {
+        let (width, height) = (self.bounds.width, self.bounds.height);
+        let edge = rectangle_edge(0, 0, width - 1, height - 1);
+
+        let first_bottom = mesh.vertices.len();
+        for &(x, y) in &edge {
+            let [x, y, _] = mesh.vertices[self.vertex(x, y)];
+            mesh.vertices.push([x, y, -thickness]);
+        }
+        let floor_center = mesh.vertices.len();
+        mesh.vertices.push([(width - 1) as f64 / 2.0, (height - 1) as f64 / 2.0, -thickness]);
+
+        for i in 0..edge.len() {
+            let next = (i + 1) % edge.len();
+            let (top, next_top) =
+                (self.vertex(edge[i].0, edge[i].1), self.vertex(edge[next].0, edge[next].1));
+            let (bottom, next_bottom) = (first_bottom + i, first_bottom + next);
+            mesh.triangles.push([bottom, next_bottom, next_top]);
+            mesh.triangles.push([bottom, next_top, top]);
+            // The floor faces down, so it winds clockwise seen from above
+            mesh.triangles.push([floor_center, next_bottom, bottom]);
+        }
+    }
+}
+
+/// The pixels along the edge of the rectangle from `(x0, y0)` to `(x1, y1)`, counterclockwise
+/// seen from above (with rows numbered downwards), starting from the lower left corner
+fn rectangle_edge(x0: usize, y0: usize, x1: usize, y1: usize) -> Vec<(usize, usize)> {
+    let bottom = (x0..x1).map(|x| (x, y1));
+    let right = (y0 + 1..=y1).rev().map(|y| (x1, y));
+    let top = (x0 + 1..=x1).rev().map(|x| (x, y0));
+    let left = (y0..y1).map(|y| (x0, y));
+    bottom.chain(right).chain(top).chain(left).collect()
+}
+
+/// The file formats a mesh can be written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshFormat {
+    Obj,
+    /// Binary STL, as most slicers expect
+    Stl,
+    /// ASCII PLY
+    Ply,
+}
+
+impl MeshFormat {
+    /// Work out the format from the extension of `path`
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("obj") => Ok(MeshFormat::Obj),
+            Some("stl") => Ok(MeshFormat::Stl),
+            Some("ply") => Ok(MeshFormat::Ply),
+            _ => Err(format!("unknown mesh format for {}; use .obj, .stl or .ply", path)),
+        }
+    }
+}
+
+/// A triangle mesh, each triangle counterclockwise seen from outside
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    pub fn write<W: Write>(&self, format: MeshFormat, out: &mut W) -> io::Result<()> {
+        match format {
+            MeshFormat::Obj => self.write_obj(out),
+            MeshFormat::Stl => self.write_stl(out),
+            MeshFormat::Ply => self.write_ply(out),
+        }
+    }
+
+    fn write_obj<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for [x, y, z] in &self.vertices {
+            writeln!(out, "v {} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in &self.triangles {
+            // OBJ numbers vertices from 1
+            writeln!(out, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+
+    fn write_stl<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&[0; 80])?;
+        out.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|index| self.vertices[index]);
+            for value in normal(a, b, c).iter().chain(a.iter()).chain(b.iter()).chain(c.iter()) {
+                out.write_all(&(*value as f32).to_le_bytes())?;
+            }
+            // The attribute byte count, which nothing uses
+            out.write_all(&[0; 2])?;
+        }
+        Ok(())
+    }
+
+    fn write_ply<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "ply")?;
+        writeln!(out, "format ascii 1.0")?;
+        writeln!(out, "element vertex {}", self.vertices.len())?;
+        for axis in ["x", "y", "z"] {
+            writeln!(out, "property double {}", axis)?;
+        }
+        writeln!(out, "element face {}", self.triangles.len())?;
+        writeln!(out, "property list uchar uint vertex_indices")?;
+        writeln!(out, "end_header")?;
+        for [x, y, z] in &self.vertices {
+            writeln!(out, "{} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in &self.triangles {
+            writeln!(out, "3 {} {} {}", a, b, c)?;
+        }
+        Ok(())
+    }
+}
+
+/// The unit normal of the triangle `a b c`, or zero if it is degenerate
+fn normal(a: [f64; 3], b: [f64; 3], c: [f64; 3]) -> [f64; 3] {
+    let (u, v) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+    let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if length == 0.0 {
+        [0.0; 3]
+    } else {
+        n.map(|component| component / length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A 6x5 heightmap: a flat plateau on the left and a ramp on the right
+    fn plateau() -> Heightmap {
+        let bounds = Dimensions::new(6, 5);
+        let elevations: Vec<Option<f64>> = (0..bounds.total_pixels())
+            .map(|index| match index % bounds.width {
+                x if x < 3 => None,
+                x => Some(x as f64),
+            })
+            .collect();
+        Heightmap::new(&elevations, bounds, 10.0, Some((4.0, 6.0)))
+    }
+
+    /// Whether every edge of the mesh is shared by exactly two triangles, running opposite ways
+    fn is_closed(mesh: &Mesh) -> bool {
+        let mut edges: HashMap<(usize, usize), i32> = HashMap::new();
+        for &[a, b, c] in &mesh.triangles {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((from, to)).or_insert(0) += 1;
+            }
+        }
+        edges.iter().all(|(&(from, to), &count)| count == 1 && edges.get(&(to, from)) == Some(&1))
+    }
+
+    #[test]
+    fn test_distance_field() {
+        // 2^-ν grows with the distance to a set just left of the image, so the estimate is that
+        // distance
+        let bounds = Dimensions::new(20, 3);
+        let values: Vec<Option<f64>> = (0..bounds.total_pixels())
+            .map(|index| Some(-((index % bounds.width) as f64 + 1.0).log2()))
+            .collect();
+        let distances = distance_field(&values, bounds);
+        let distance = distances[bounds.width + 10].unwrap();
+        assert!((distance - 11.0).abs() < 0.1);
+
+        assert_eq!(distance_field(&[None, Some(1.0)], Dimensions::new(2, 1))[0], None);
+    }
+
+    #[test]
+    fn test_heights() {
+        let heightmap = plateau();
+        // The plateau is the top, and the foot of the ramp is clamped to the bottom of the range
+        assert_eq!(heightmap.height(0, 0), 10.0);
+        assert_eq!(heightmap.height(3, 0), 0.0);
+        assert_eq!(heightmap.height(5, 4), 5.0);
+
+        // The range defaults to that of the elevations
+        let elevations = [Some(1.0), Some(3.0), Some(f64::NEG_INFINITY), None];
+        let heightmap = Heightmap::new(&elevations, Dimensions::new(2, 2), 1.0, None);
+        assert_eq!(heightmap.heights, vec![0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_mesh() {
+        let heightmap = plateau();
+        let mesh = heightmap.mesh(false, None);
+        assert_eq!(mesh.vertices.len(), 30);
+        assert_eq!(mesh.triangles.len(), 2 * 5 * 4);
+        // The upper left pixel is at the top left of the mesh, and the surface faces up
+        assert_eq!(mesh.vertices[0], [0.0, 4.0, 10.0]);
+        let [a, b, c] = mesh.triangles[0].map(|index| mesh.vertices[index]);
+        assert!(normal(a, b, c)[2] > 0.0);
+
+        // The plateau is two cells wide, and the fans over it take fewer triangles
+        let decimated = heightmap.mesh(true, None);
+        assert!(decimated.triangles.len() < mesh.triangles.len());
+
+        for mesh in [heightmap.mesh(false, Some(1.0)), heightmap.mesh(true, Some(1.0))] {
+            assert!(is_closed(&mesh));
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let mesh = plateau().mesh(true, Some(2.0));
+
+        let mut stl = Vec::new();
+        mesh.write(MeshFormat::Stl, &mut stl).unwrap();
+        assert_eq!(stl.len(), 84 + 50 * mesh.triangles.len());
+
+        let mut obj = Vec::new();
+        mesh.write(MeshFormat::Obj, &mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(obj.lines().count(), mesh.vertices.len() + mesh.triangles.len());
+        assert!(obj.starts_with("v 0 4 10\n"));
+
+        let mut ply = Vec::new();
+        mesh.write(MeshFormat::Ply, &mut ply).unwrap();
+        let ply = String::from_utf8(ply).unwrap();
+        assert!(ply.contains(&format!("element face {}\n", mesh.triangles.len())));
+
+        assert_eq!(MeshFormat::from_path("relief.STL"), Ok(MeshFormat::Stl));
+        assert!(MeshFormat::from_path("relief.png").is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::types::Dimensions;
+
+/// A grayscale image made of equally sized tiles laid out in a grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Montage {
+    pub pixels: Vec<u8>,
+    pub grid: Dimensions,
+    pub tile: Dimensions,
+}
+
+impl Montage {
+    /// A black montage of `grid` columns and rows of `tile` sized tiles
+    pub fn new(grid: Dimensions, tile: Dimensions) -> Self {
+        Self { pixels: vec![0; grid.total_pixels() * tile.total_pixels()], grid, tile }
+    }
+
+    /// Dimensions of the whole image
+    pub fn bounds(&self) -> Dimensions {
+        Dimensions::new(self.grid.width * self.tile.width, self.grid.height * self.tile.height)
+    }
+
+    /// Copy `tile` into the grid cell at `column`, `row`
+    pub fn place(&mut self, column: usize, row: usize, tile: &[u8]) {
+        assert_eq!(tile.len(), self.tile.total_pixels());
+        assert!(column < self.grid.width && row < self.grid.height);
+
+        let width = self.bounds().width;
+        for (y, line) in tile.chunks(self.tile.width).enumerate() {
+            let start = (row * self.tile.height + y) * width + column * self.tile.width;
+            self.pixels[start..start + self.tile.width].copy_from_slice(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_tiles() {
+        let mut montage = Montage::new(Dimensions::new(2, 2), Dimensions::new(2, 1));
+        assert_eq!(montage.bounds(), Dimensions::new(4, 2));
+
+        montage.place(1, 0, &[1, 2]);
+        montage.place(0, 1, &[3, 4]);
+        assert_eq!(montage.pixels, vec![0, 0, 1, 2, 3, 4, 0, 0]);
+    }
+}
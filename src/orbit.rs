@@ -0,0 +1,143 @@
+use crate::algorithms::PlottingAlgorithm;
+use num::complex::Complex;
+
+/// How close a point must come back to the final point of a bounded orbit to count as a cycle
+const PERIOD_TOLERANCE: f64 = 1e-9;
+
+/// The full orbit of a single point, with a summary of how it behaves
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitReport {
+    /// Every value of `z`, starting with `z0`
+    pub points: Vec<Complex<f64>>,
+    /// The iteration the orbit escaped on, if it did
+    pub escape: Option<usize>,
+    /// The period of the cycle a bounded orbit settles into, if it settles within the limit
+    pub period: Option<usize>,
+    pub min_norm: f64,
+    pub max_norm: f64,
+}
+
+impl OrbitReport {
+    /// Iterate `algorithm` from `z0` with parameter `c`, up to `limit` times
+    pub fn trace(
+        algorithm: &dyn PlottingAlgorithm, z0: Complex<f64>, c: Complex<f64>, limit: usize,
+    ) -> Self {
+        let mut points = vec![z0];
+        let escape = algorithm.orbit_from(z0, c, limit, &mut |z| points.push(z));
+        let period = match escape {
+            Some(_) => None,
+            None => detect_period(&points),
+        };
+
+        let norms = points.iter().map(|z| z.norm());
+        let min_norm = norms.clone().fold(f64::INFINITY, f64::min);
+        let max_norm = norms.fold(0.0, f64::max);
+
+        Self { points, escape, period, min_norm, max_norm }
+    }
+
+    /// One row per point: `iteration,re,im,norm`
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("iteration,re,im,norm\n");
+        for (i, z) in self.points.iter().enumerate() {
+            csv.push_str(&format!("{},{},{},{}\n", i, z.re, z.im, z.norm()));
+        }
+        csv
+    }
+
+    /// The summary and the points as a JSON object
+    pub fn to_json(&self) -> String {
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .map(|z| format!("[{},{}]", json_number(z.re), json_number(z.im)))
+            .collect();
+
+        format!(
+            "{{\"escape\":{},\"period\":{},\"min_norm\":{},\"max_norm\":{},\"points\":[{}]}}\n",
+            self.escape.map_or("null".to_string(), |n| n.to_string()),
+            self.period.map_or("null".to_string(), |n| n.to_string()),
+            json_number(self.min_norm),
+            json_number(self.max_norm),
+            points.join(",")
+        )
+    }
+}
+
+/// JSON has no infinities or NaN, so those become `null`
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// The smallest period `p` for which the final point of `points` came back within
+/// `PERIOD_TOLERANCE` of itself `p` steps earlier
+pub fn detect_period(points: &[Complex<f64>]) -> Option<usize> {
+    let (last, earlier) = points.split_last()?;
+    let tolerance = PERIOD_TOLERANCE * last.norm().max(1.0);
+
+    earlier.iter().rev().position(|z| (z - last).norm() <= tolerance).map(|steps| steps + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::{EscapeTime, Julia};
+
+    #[test]
+    fn test_trace_escaping_orbit() {
+        let mandelbrot = EscapeTime::default();
+        let report =
+            OrbitReport::trace(&mandelbrot, Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), 100);
+        assert_eq!(report.escape, Some(4));
+        assert_eq!(report.period, None);
+        assert_eq!(report.points.len(), 5);
+        assert_eq!(report.points[3], Complex::new(5.0, 0.0));
+        assert_eq!((report.min_norm, report.max_norm), (0.0, 26.0));
+    }
+
+    #[test]
+    fn test_trace_periodic_orbit() {
+        let mandelbrot = EscapeTime::default();
+        // The critical orbit of -1 alternates between 0 and -1
+        let report =
+            OrbitReport::trace(&mandelbrot, Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0), 50);
+        assert_eq!(report.escape, None);
+        assert_eq!(report.period, Some(2));
+        assert_eq!((report.min_norm, report.max_norm), (0.0, 1.0));
+
+        // Attracted to a fixed point, rather than landing on it
+        let report =
+            OrbitReport::trace(&mandelbrot, Complex::new(0.0, 0.0), Complex::new(0.1, 0.1), 1000);
+        assert_eq!(report.period, Some(1));
+    }
+
+    #[test]
+    fn test_julia_orbit() {
+        let mandelbrot = EscapeTime::default();
+        let julia = Julia { algorithm: Box::new(EscapeTime::default()), c: Complex::new(0.0, 0.0) };
+        assert_eq!(julia.calculate(Complex::new(0.5, 0.0), 0, 100), None);
+        assert_eq!(julia.calculate(Complex::new(2.0, 0.0), 0, 100), Some(2));
+
+        // i squares to -1, then settles on the repelling fixed point 1
+        let report =
+            OrbitReport::trace(&mandelbrot, Complex::new(0.0, 1.0), Complex::new(0.0, 0.0), 10);
+        assert_eq!(report.points[1..3], [Complex::new(-1.0, 0.0), Complex::new(1.0, 0.0)]);
+        assert_eq!(report.period, Some(1));
+    }
+
+    #[test]
+    fn test_json() {
+        let mandelbrot = EscapeTime::default();
+        let report =
+            OrbitReport::trace(&mandelbrot, Complex::new(0.0, 0.0), Complex::new(-1.0, 0.0), 2);
+        assert_eq!(
+            report.to_json(),
+            "{\"escape\":null,\"period\":2,\"min_norm\":0,\"max_norm\":1,\"points\":[[0,0],[-1,0],[0,0]]}\n"
+        );
+        assert!(report.to_csv().starts_with("iteration,re,im,norm\n0,0,0,0\n1,-1,0,1\n"));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::mandelbrot::ComplexRegion;
+use crate::types::Dimensions;
+use num::complex::Complex;
+
+/// Draw the line through `points` over a grayscale image of `region`, setting the pixels it
+/// crosses to `value`. Segments are clipped to the image, so points may lie far outside of it.
+pub fn draw_polyline(
+    pixels: &mut [u8], bounds: Dimensions, region: ComplexRegion, points: &[Complex<f64>],
+    value: u8,
+) {
+    assert_eq!(pixels.len(), bounds.total_pixels());
+
+    for segment in points.windows(2) {
+        let start = region.point_to_position(bounds, segment[0]);
+        let end = region.point_to_position(bounds, segment[1]);
+        let Some((start, end)) = clip(start, end, bounds) else {
+            continue;
+        };
+
+        let steps = (end.0 - start.0).abs().max((end.1 - start.1).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            let x = start.0 + (end.0 - start.0) * t;
+            let y = start.1 + (end.1 - start.1) * t;
+            if x >= 0.0 && y >= 0.0 && x < bounds.width as f64 && y < bounds.height as f64 {
+                pixels[y as usize * bounds.width + x as usize] = value;
+            }
+        }
+    }
+}
+
+/// Clip the segment from `start` to `end` to the image with the Liang-Barsky algorithm,
+/// returning `None` if it misses the image entirely
+fn clip(
+    start: (f64, f64), end: (f64, f64), bounds: Dimensions,
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+
+    for (p, q) in [
+        (-dx, start.0),
+        (dx, bounds.width as f64 - start.0),
+        (-dy, start.1),
+        (dy, bounds.height as f64 - start.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            enter = enter.max(q / p);
+        } else {
+            exit = exit.min(q / p);
+        }
+    }
+
+    if enter > exit {
+        return None;
+    }
+    Some((
+        (start.0 + dx * enter, start.1 + dy * enter),
+        (start.0 + dx * exit, start.1 + dy * exit),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region() -> ComplexRegion {
+        ComplexRegion::new(Complex::new(0.0, 4.0), Complex::new(4.0, 0.0))
+    }
+
+    #[test]
+    fn test_draw_polyline() {
+        let bounds = Dimensions::new(4, 4);
+        let mut pixels = vec![0u8; 16];
+        let points = [Complex::new(0.5, 3.5), Complex::new(3.5, 3.5), Complex::new(3.5, 0.5)];
+
+        draw_polyline(&mut pixels, bounds, region(), &points, 255);
+
+        #[rustfmt::skip]
+        assert_eq!(pixels, vec![
+            255, 255, 255, 255,
+              0,   0,   0, 255,
+              0,   0,   0, 255,
+              0,   0,   0, 255,
+        ]);
+    }
+
+    #[test]
+    fn test_draw_polyline_clips_distant_points() {
+        let bounds = Dimensions::new(4, 4);
+        let mut pixels = vec![0u8; 16];
+        let points = [Complex::new(-1e9, 1.5), Complex::new(1e9, 1.5)];
+
+        draw_polyline(&mut pixels, bounds, region(), &points, 9);
+        assert_eq!(&pixels[8..12], &[9, 9, 9, 9]);
+        assert_eq!(pixels.iter().filter(|&&value| value == 9).count(), 4);
+
+        // Entirely outside of the image
+        let mut pixels = vec![0u8; 16];
+        draw_polyline(&mut pixels, bounds, region(), &[Complex::new(5.0, 5.0), Complex::new(9.0, 1.0)], 9);
+        assert!(pixels.iter().all(|&value| value == 0));
+    }
+}
@@ -15,12 +15,49 @@ pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
     }
 }
 
-#[allow(dead_code)]
+/// Parse exactly `count` values separated by `separator`, like the coordinates `1,-2.5,3`
+pub fn parse_list<T: FromStr>(s: &str, separator: char, count: usize) -> Option<Vec<T>> {
+    let values: Vec<T> =
+        s.split(separator).map(|part| T::from_str(part).ok()).collect::<Option<_>>()?;
+    if values.len() == count {
+        Some(values)
+    } else {
+        None
+    }
+}
+
 pub fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    match parse_pair(s, ',') {
-        Some((re, im)) => Some(Complex { re, im }),
-        None => None,
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
+
+/// Parse a parameter file into the command line arguments it stands for.
+///
+/// Each line holds either `key = value`, which becomes `--key=value`, or a bare `key` for flags,
+/// which becomes `--key`. Blank lines and lines starting with `#` are ignored.
+pub fn parse_params(s: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+
+    for (number, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim())),
+            None => (line, None),
+        };
+        if key.is_empty() || key.starts_with('-') || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid parameter '{}'", number + 1, line));
+        }
+
+        args.push(match value {
+            Some(value) => format!("--{}={}", key, value),
+            None => format!("--{}", key),
+        });
     }
+
+    Ok(args)
 }
 
 #[cfg(test)]
@@ -38,9 +75,40 @@ pub mod tests {
         assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
     }
 
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list::<f64>("1,-2.5,3", ',', 3), Some(vec![1.0, -2.5, 3.0]));
+        assert_eq!(parse_list::<f64>("1,2", ',', 3), None);
+        assert_eq!(parse_list::<f64>("1,2,3,4", ',', 3), None);
+        assert_eq!(parse_list::<f64>("1,,3", ',', 3), None);
+    }
+
     #[test]
     fn test_parse_complex() {
         assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex { re: 1.25, im: -0.0625 }));
         assert_eq!(parse_complex(",-0.0625"), None)
     }
+
+    #[test]
+    fn test_parse_params() {
+        let params = "
+            # a trapped view
+            zoom = 3.0
+            center=-0.5,0.0
+
+            trap = circle:0,0,0.5
+            invert
+        ";
+        assert_eq!(
+            parse_params(params),
+            Ok(vec![
+                "--zoom=3.0".to_string(),
+                "--center=-0.5,0.0".to_string(),
+                "--trap=circle:0,0,0.5".to_string(),
+                "--invert".to_string(),
+            ])
+        );
+        assert!(parse_params("two words").is_err());
+        assert!(parse_params("--zoom = 1").is_err());
+    }
 }
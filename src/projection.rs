@@ -0,0 +1,215 @@
+use crate::mandelbrot::{ComplexRegion, ExponentialMap};
+use crate::types::{Dimensions, Pixel};
+use num::complex::Complex;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+/// How the pixels of an image map to the complex plane. Any algorithm can be drawn through any
+/// projection with `Canvas::render_projected`.
+pub trait Projection {
+    /// The point shown at `pixel` of an image of `bounds`, or `None` where the image shows no
+    /// point of the plane
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>>;
+}
+
+/// The axis-aligned linear map the renderer uses by default
+impl Projection for ComplexRegion {
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        Some(ComplexRegion::pixel_to_point(self, bounds, pixel))
+    }
+}
+
+/// The strip of an exponential map, which has its own size
+impl Projection for ExponentialMap {
+    fn pixel_to_point(&self, _bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        Some(ExponentialMap::pixel_to_point(self, pixel))
+    }
+}
+
+/// A linear view of `region` turned counterclockwise by `angle` radians around its center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rotated {
+    pub region: ComplexRegion,
+    pub angle: f64,
+}
+
+impl Projection for Rotated {
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        let center = (self.region.upper_left + self.region.lower_right) / 2.0;
+        let w = self.region.pixel_to_point(bounds, pixel);
+        Some(center + (w - center) * Complex::from_polar(1.0, self.angle))
+    }
+}
+
+/// The Möbius transform `(a w + b) / (c w + d)` of a linear view of `region`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mobius {
+    pub region: ComplexRegion,
+    pub a: Complex<f64>,
+    pub b: Complex<f64>,
+    pub c: Complex<f64>,
+    pub d: Complex<f64>,
+}
+
+impl Mobius {
+    /// The inversion `1 / w`, which turns the plane inside out: the far exterior gathers around
+    /// 0 and the set spreads out around it
+    pub fn inversion(region: ComplexRegion) -> Self {
+        let (zero, one) = (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0));
+        Self { region, a: zero, b: one, c: one, d: zero }
+    }
+}
+
+impl Projection for Mobius {
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        let w = self.region.pixel_to_point(bounds, pixel);
+        Some((self.a * w + self.b) / (self.c * w + self.d))
+    }
+}
+
+/// The Riemann sphere, with `center` at its south pole, infinity at its north pole and the circle
+/// of `radius` around `center` on its equator. The view is turned counterclockwise by `yaw`
+/// radians around the poles, as `Rotated` turns a linear view, and then the sphere is tipped by
+/// `pitch` radians, bringing its north pole up and over towards the viewer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Complex<f64>,
+    pub radius: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+}
+
+impl Sphere {
+    /// The point at the position `(x, y, z)` on the unit sphere as it is seen, with z towards
+    /// the viewer, by stereographic projection from the north pole
+    fn project(&self, (x, y, z): (f64, f64, f64)) -> Complex<f64> {
+        // Undo the pitch, around the x axis, and then the yaw, around the poles
+        let (sin, cos) = self.pitch.sin_cos();
+        let (y, z) = (y * cos + z * sin, z * cos - y * sin);
+        // The south pole faces the viewer before the sphere is tipped
+        let (north, east) = (-z, Complex::new(x, y) * Complex::from_polar(1.0, self.yaw));
+        self.center + east * self.radius / (1.0 - north)
+    }
+}
+
+/// The sphere seen from outside, as a disk filling the shorter side of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereographic(pub Sphere);
+
+impl Projection for Stereographic {
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        let radius = bounds.width.min(bounds.height) as f64 / 2.0;
+        let x = (pixel.x as f64 - bounds.width as f64 / 2.0) / radius;
+        let y = (bounds.height as f64 / 2.0 - pixel.y as f64) / radius;
+        let depth = 1.0 - x * x - y * y;
+        if depth < 0.0 {
+            return None;
+        }
+        Some(self.0.project((x, y, depth.sqrt())))
+    }
+}
+
+/// The sphere seen from inside, as an equirectangular panorama for 360° viewers: across the
+/// image goes once around the horizon, and down it goes from the zenith, at infinity, to the
+/// nadir, at the center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Equirectangular(pub Sphere);
+
+impl Projection for Equirectangular {
+    fn pixel_to_point(&self, bounds: Dimensions, pixel: Pixel) -> Option<Complex<f64>> {
+        let longitude = pixel.x as f64 / bounds.width as f64 * TAU - PI;
+        let latitude = FRAC_PI_2 - pixel.y as f64 / bounds.height as f64 * PI;
+        // Looking straight ahead with no pitch, the horizon is the equator and up is north
+        let (x, y) = (latitude.cos() * longitude.sin(), latitude.sin());
+        let z = latitude.cos() * longitude.cos();
+        let sphere = Sphere { pitch: self.0.pitch + FRAC_PI_2, ..self.0 };
+        Some(sphere.project((x, y, z)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::EscapeTime;
+    use crate::coloring::Coloring;
+    use crate::mandelbrot::{calculate_region, Canvas};
+
+    fn close(a: Option<Complex<f64>>, b: Complex<f64>) -> bool {
+        (a.unwrap() - b).norm() < 1e-9
+    }
+
+    #[test]
+    fn test_linear_projections() {
+        let region = calculate_region(4.0, (1.0, 1.0));
+        let bounds = Dimensions::new(4, 4);
+        let pixel = Pixel::new(3, 2);
+        let w = region.pixel_to_point(bounds, pixel);
+        assert_eq!(Projection::pixel_to_point(&region, bounds, pixel), Some(w));
+
+        // A quarter turn shows the top middle of the region at the right middle of the image
+        let rotated = Rotated { region, angle: FRAC_PI_2 };
+        assert!(close(rotated.pixel_to_point(bounds, Pixel::new(4, 2)), Complex::new(1.0, 3.0)));
+
+        assert!(close(Mobius::inversion(region).pixel_to_point(bounds, pixel), 1.0 / w));
+        let (one, two) = (Complex::new(1.0, 0.0), Complex::new(2.0, 0.0));
+        let identity = Mobius { region, a: two, b: 0.0 * one, c: 0.0 * one, d: two };
+        assert!(close(identity.pixel_to_point(bounds, pixel), w));
+    }
+
+    #[test]
+    fn test_stereographic() {
+        let sphere = Sphere { center: Complex::new(-0.5, 0.0), radius: 2.0, yaw: 0.0, pitch: 0.0 };
+        let bounds = Dimensions::new(200, 100);
+        let projection = Stereographic(sphere);
+        // The middle of the disk is the center, its edge the equator, and beyond it is nothing
+        assert!(close(projection.pixel_to_point(bounds, Pixel::new(100, 50)), sphere.center));
+        let edge = projection.pixel_to_point(bounds, Pixel::new(150, 50)).unwrap();
+        assert!(((edge - sphere.center).norm() - 2.0).abs() < 1e-9);
+        assert_eq!(projection.pixel_to_point(bounds, Pixel::new(0, 0)), None);
+
+        // Turning the view a quarter turn shows the top of the equator on the right
+        let projection = Stereographic(Sphere { yaw: FRAC_PI_2, ..sphere });
+        let right = projection.pixel_to_point(bounds, Pixel::new(150, 50)).unwrap();
+        assert!((right - sphere.center - Complex::new(0.0, 2.0)).norm() < 1e-9);
+
+        // Tipping the sphere a quarter turn shows the equator in the middle of the disk, and the
+        // north pole, at infinity, at its top, where points race away
+        let projection = Stereographic(Sphere { pitch: FRAC_PI_2, ..sphere });
+        let middle = projection.pixel_to_point(bounds, Pixel::new(100, 50)).unwrap();
+        assert!(((middle - sphere.center).norm() - 2.0).abs() < 1e-9);
+        assert!(projection.pixel_to_point(bounds, Pixel::new(100, 1)).unwrap().norm() > 10.0);
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let sphere = Sphere { center: Complex::new(0.0, 0.0), radius: 1.0, yaw: 0.0, pitch: 0.0 };
+        let bounds = Dimensions::new(360, 180);
+        let projection = Equirectangular(sphere);
+        // The horizon is the equator, the nadir the center and the zenith infinity
+        for x in [0, 90, 200] {
+            let point = projection.pixel_to_point(bounds, Pixel::new(x, 90)).unwrap();
+            assert!((point.norm() - 1.0).abs() < 1e-9);
+        }
+        assert!(projection.pixel_to_point(bounds, Pixel::new(10, 179)).unwrap().norm() < 0.01);
+        assert!(projection.pixel_to_point(bounds, Pixel::new(10, 1)).unwrap().norm() > 100.0);
+    }
+
+    #[test]
+    fn test_render_projected() {
+        let canvas = Canvas::new(Box::<EscapeTime>::default(), Coloring::Iterations);
+        let sphere = Sphere { center: Complex::new(-0.5, 0.0), radius: 2.0, yaw: 0.0, pitch: 0.0 };
+        let bounds = Dimensions::new(40, 40);
+        let mut pixels = vec![1; bounds.total_pixels()];
+        canvas.render_projected(&mut pixels, bounds, 0, &Stereographic(sphere), 100, false);
+
+        // The center of the disk is in the main cardioid, the corners are off the sphere, and
+        // the top of the disk is far out
+        assert_eq!(pixels[20 * 40 + 20], 0);
+        assert_eq!(pixels[0], 0);
+        assert!(pixels[40 + 20] > 0);
+
+        // Rows can be rendered a band at a time
+        let mut band = vec![1; 40];
+        canvas.render_projected(&mut band, bounds, 1, &Stereographic(sphere), 100, false);
+        assert_eq!(band, pixels[40..80]);
+    }
+}
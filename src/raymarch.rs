@@ -0,0 +1,384 @@
+use rayon::prelude::*;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::types::Dimensions;
+
+/// Beyond this distance from the camera a ray is taken to have missed
+const MAX_DISTANCE: f64 = 20.0;
+
+/// A ray hits the surface once the distance estimate falls below this share of the distance
+/// travelled, so the detail resolved matches the size of a pixel at that depth
+const HIT_THRESHOLD: f64 = 5e-4;
+
+/// Bailout radius of the distance estimators' orbits
+const BAILOUT: f64 = 4.0;
+
+/// Samples taken along the normal for ambient occlusion, and the spacing between them
+const OCCLUSION_SAMPLES: usize = 5;
+const OCCLUSION_SPACING: f64 = 0.02;
+
+/// The light comes from the upper left, behind the camera
+const LIGHT_DIRECTION: Vec3 = Vec3::new(-0.5, 0.8, -0.6);
+
+/// Colors of the surface facing the light, the surface in shade, and the sky at its top and
+/// bottom
+const SURFACE_COLOR: Vec3 = Vec3::new(1.0, 0.78, 0.5);
+const SHADE_COLOR: Vec3 = Vec3::new(0.22, 0.28, 0.45);
+const SKY_TOP: Vec3 = Vec3::new(0.05, 0.06, 0.1);
+const SKY_BOTTOM: Vec3 = Vec3::new(0.25, 0.28, 0.35);
+
+/// A point or direction in space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, other: Vec3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(self) -> Vec3 {
+        self * (1.0 / self.length())
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f64> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, scale: f64) -> Vec3 {
+        Vec3::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        self * -1.0
+    }
+}
+
+/// A 3D fractal with a distance estimator: a lower bound on the distance from any point to its
+/// surface, which rays can safely step by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    /// The Mandelbulb, `z → z^power + c` in spherical coordinates
+    Mandelbulb { power: f64, iterations: usize },
+    /// The slice through the real and first two imaginary axes of the quaternion Julia set of
+    /// `q → q² + c`
+    QuaternionJulia { c: [f64; 4], iterations: usize },
+}
+
+impl Shape {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match *self {
+            Shape::Mandelbulb { power, iterations } => mandelbulb_distance(p, power, iterations),
+            Shape::QuaternionJulia { c, iterations } => quaternion_julia_distance(p, c, iterations),
+        }
+    }
+
+    /// The surface normal at `p`, from the gradient of the distance estimate
+    fn normal(&self, p: Vec3, h: f64) -> Vec3 {
+        let (dx, dy, dz) = (Vec3::new(h, 0.0, 0.0), Vec3::new(0.0, h, 0.0), Vec3::new(0.0, 0.0, h));
+        Vec3::new(
+            self.distance(p + dx) - self.distance(p - dx),
+            self.distance(p + dy) - self.distance(p - dy),
+            self.distance(p + dz) - self.distance(p - dz),
+        )
+        .normalized()
+    }
+}
+
+/// The distance estimate `r ln r / (2 dr)` of the Mandelbulb, where `dr` is the running
+/// derivative of the orbit
+fn mandelbulb_distance(p: Vec3, power: f64, iterations: usize) -> f64 {
+    let (mut z, mut dr, mut r) = (p, 1.0, p.length());
+
+    for _ in 0..iterations {
+        r = z.length();
+        if r > BAILOUT {
+            break;
+        }
+        if r == 0.0 {
+            // The orbit sits on the origin, deep inside
+            return 0.0;
+        }
+
+        let theta = (z.z / r).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let scale = r.powf(power);
+        z = Vec3::new(
+            theta.sin() * phi.cos() * scale,
+            theta.sin() * phi.sin() * scale,
+            theta.cos() * scale,
+        ) + p;
+    }
+
+    0.5 * r.ln() * r / dr
+}
+
+/// The distance estimate `|q| ln |q| / (2 |q'|)` of a quaternion Julia set, with the point as
+/// a quaternion whose last component is 0
+fn quaternion_julia_distance(p: Vec3, c: [f64; 4], iterations: usize) -> f64 {
+    let mut q = [p.x, p.y, p.z, 0.0];
+    let mut derivative = 1.0;
+    let mut norm_sqr: f64 = q.iter().map(|v| v * v).sum();
+
+    for _ in 0..iterations {
+        if norm_sqr > BAILOUT * BAILOUT {
+            break;
+        }
+        // |q²|' = 2 |q| |q'|, as the quaternion norm is multiplicative
+        derivative *= 2.0 * norm_sqr.sqrt();
+        q = [
+            q[0] * q[0] - q[1] * q[1] - q[2] * q[2] - q[3] * q[3] + c[0],
+            2.0 * q[0] * q[1] + c[1],
+            2.0 * q[0] * q[2] + c[2],
+            2.0 * q[0] * q[3] + c[3],
+        ];
+        norm_sqr = q.iter().map(|v| v * v).sum();
+    }
+
+    let norm = norm_sqr.sqrt();
+    0.5 * norm * norm.ln() / derivative
+}
+
+/// A pinhole camera at `position`, looking at `target`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Horizontal field of view, in degrees
+    pub field_of_view: f64,
+}
+
+impl Camera {
+    /// The direction of the ray through the center of `pixel`, with y pointing up in the image
+    pub fn ray(&self, bounds: Dimensions, x: usize, y: usize) -> Vec3 {
+        let forward = (self.target - self.position).normalized();
+        // Fall back to another up vector when looking straight up or down
+        let up = match forward.cross(Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9 {
+            true => Vec3::new(0.0, 0.0, 1.0),
+            false => Vec3::new(0.0, 1.0, 0.0),
+        };
+        let right = up.cross(forward).normalized();
+        let up = forward.cross(right);
+
+        let half_width = (self.field_of_view.to_radians() / 2.0).tan();
+        let scale = 2.0 * half_width / bounds.width as f64;
+        let u = (x as f64 + 0.5 - bounds.width as f64 / 2.0) * scale;
+        let v = (bounds.height as f64 / 2.0 - y as f64 - 0.5) * scale;
+
+        (forward + right * u + up * v).normalized()
+    }
+}
+
+/// Everything needed to ray march a shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scene {
+    pub shape: Shape,
+    pub camera: Camera,
+    /// Steps a ray may take before it is taken to have missed
+    pub max_steps: usize,
+    /// How sharp shadows are; larger values give harder edges, 0 turns shadows off
+    pub shadow_softness: f64,
+    /// How much creases are darkened by ambient occlusion; 0 turns it off
+    pub occlusion: f64,
+}
+
+impl Scene {
+    /// Render the scene into `pixels`, three bytes per pixel, a row per rayon task
+    pub fn render(&self, pixels: &mut [u8], bounds: Dimensions) {
+        assert_eq!(pixels.len(), bounds.total_pixels() * 3);
+
+        pixels.par_chunks_mut(bounds.width * 3).enumerate().for_each(|(y, row)| {
+            for x in 0..bounds.width {
+                let color = self.shade(self.camera.ray(bounds, x, y));
+                for (channel, value) in
+                    row[x * 3..x * 3 + 3].iter_mut().zip([color.x, color.y, color.z])
+                {
+                    *channel = (value.clamp(0.0, 1.0) * u8::MAX as f64).round() as u8;
+                }
+            }
+        });
+    }
+
+    /// March from `origin` along `direction`, returning the distance travelled to the surface
+    pub fn march(&self, origin: Vec3, direction: Vec3) -> Option<f64> {
+        let mut t = 0.0;
+        for _ in 0..self.max_steps {
+            let distance = self.shape.distance(origin + direction * t);
+            if distance < HIT_THRESHOLD * t.max(1.0) {
+                return Some(t);
+            }
+            t += distance;
+            if t > MAX_DISTANCE {
+                break;
+            }
+        }
+        None
+    }
+
+    /// The color seen along a ray from the camera
+    fn shade(&self, direction: Vec3) -> Vec3 {
+        let origin = self.camera.position;
+        let Some(t) = self.march(origin, direction) else {
+            let height = 0.5 + 0.5 * direction.y;
+            return SKY_BOTTOM * (1.0 - height) + SKY_TOP * height;
+        };
+
+        let epsilon = HIT_THRESHOLD * t.max(1.0);
+        let point = origin + direction * t;
+        let normal = self.shape.normal(point, epsilon);
+        // Start the secondary rays just off the surface, so they don't hit it straight away
+        let surface = point + normal * (2.0 * epsilon);
+        let light = LIGHT_DIRECTION.normalized();
+
+        let diffuse = normal.dot(light).max(0.0) * self.soft_shadow(surface, light);
+        let occlusion = self.ambient_occlusion(surface, normal);
+        let ambient = 0.25 * occlusion;
+        SURFACE_COLOR * diffuse + SHADE_COLOR * ambient
+    }
+
+    /// How much light reaches `point` from the direction `light`, from 0 in full shadow to 1.
+    /// Rays passing close to the surface without hitting it cast a partial shadow, the more so
+    /// the closer they pass relative to the distance travelled.
+    fn soft_shadow(&self, point: Vec3, light: Vec3) -> f64 {
+        if self.shadow_softness <= 0.0 {
+            return 1.0;
+        }
+        let (mut brightness, mut t) = (1.0f64, 0.01);
+        for _ in 0..self.max_steps {
+            let distance = self.shape.distance(point + light * t);
+            if distance < HIT_THRESHOLD * 0.1 {
+                return 0.0;
+            }
+            brightness = brightness.min(self.shadow_softness * distance / t);
+            t += distance;
+            if t > MAX_DISTANCE {
+                break;
+            }
+        }
+        brightness.clamp(0.0, 1.0)
+    }
+
+    /// How open the surface at `point` is, from 0 in a deep crease to 1 on an exposed face, by
+    /// comparing the distance estimate at a few points along `normal` to how far out they are
+    fn ambient_occlusion(&self, point: Vec3, normal: Vec3) -> f64 {
+        let mut occlusion = 0.0;
+        let mut weight = 1.0;
+        for i in 1..=OCCLUSION_SAMPLES {
+            let offset = OCCLUSION_SPACING * i as f64;
+            occlusion += weight * (offset - self.shape.distance(point + normal * offset)).max(0.0);
+            weight *= 0.5;
+        }
+        (1.0 - self.occlusion * occlusion / OCCLUSION_SPACING).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene(shape: Shape) -> Scene {
+        Scene {
+            shape,
+            camera: Camera {
+                position: Vec3::new(0.0, 0.0, -3.0),
+                target: Vec3::new(0.0, 0.0, 0.0),
+                field_of_view: 40.0,
+            },
+            max_steps: 200,
+            shadow_softness: 16.0,
+            occlusion: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_vector_arithmetic() {
+        let (a, b) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(a.cross(b), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!((a + b).dot(a - b), 0.0);
+        assert_eq!((Vec3::new(3.0, 0.0, 4.0) * 2.0).length(), 10.0);
+        assert_eq!(-a, Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_camera_ray() {
+        let camera = scene(Shape::Mandelbulb { power: 8.0, iterations: 10 }).camera;
+        let bounds = Dimensions::new(3, 3);
+        // The middle pixel looks straight at the target
+        assert_eq!(camera.ray(bounds, 1, 1), Vec3::new(0.0, 0.0, 1.0));
+        // Up in the image is up in space, and right is right
+        assert!(camera.ray(bounds, 1, 0).y > 0.0);
+        assert!(camera.ray(bounds, 2, 1).x > 0.0);
+    }
+
+    #[test]
+    fn test_quaternion_julia_distance() {
+        // With c = 0 the set is the unit ball; the estimate is a lower bound on the distance
+        let shape = Shape::QuaternionJulia { c: [0.0; 4], iterations: 20 };
+        let distance = shape.distance(Vec3::new(2.0, 0.0, 0.0));
+        assert!(distance > 0.5 && distance <= 1.0);
+        assert!(shape.distance(Vec3::new(0.0, 1.01, 0.0)) < 0.01);
+    }
+
+    #[test]
+    fn test_march_hits_mandelbulb() {
+        let scene = scene(Shape::Mandelbulb { power: 8.0, iterations: 10 });
+        // The bulb reaches out to about 1.1 along each axis
+        let t = scene.march(Vec3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!(t > 1.5 && t < 2.5);
+        assert_eq!(scene.march(Vec3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 1.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let scene = scene(Shape::QuaternionJulia { c: [-0.2, 0.8, 0.0, 0.0], iterations: 12 });
+        let bounds = Dimensions::new(24, 16);
+        let mut pixels = vec![0; bounds.total_pixels() * 3];
+        scene.render(&mut pixels, bounds);
+
+        // The lower left corner sees the sky, which is bluish gray, while the set fills the
+        // middle in warm colors
+        let pixel = |x: usize, y: usize| &pixels[(y * bounds.width + x) * 3..][..3];
+        assert!(pixel(0, 15)[2] > pixel(0, 15)[0]);
+        assert!(pixel(12, 8)[0] > pixel(12, 8)[2]);
+    }
+}
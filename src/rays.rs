@@ -0,0 +1,162 @@
+use num::complex::Complex;
+use std::f64::consts::TAU;
+
+/// Escape radius the ray tracer starts from; large so the outermost point is close to the ray
+const RAY_ESCAPE_RADIUS: f64 = 65536.0;
+
+/// Newton steps allowed to reach each point of a ray
+const RAY_NEWTON_STEPS: usize = 64;
+
+/// An exact rational external angle `numerator / denominator`, measured in turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalAngle {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl ExternalAngle {
+    pub fn new(numerator: u64, denominator: u64) -> Result<Self, String> {
+        if denominator == 0 {
+            return Err("external angle denominator must not be zero".to_string());
+        }
+        Ok(Self { numerator: numerator % denominator, denominator })
+    }
+
+    /// Parse an angle written as a fraction of a turn, like `1/3`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (numerator, denominator) =
+            s.split_once('/').ok_or_else(|| format!("expected <p>/<q>, found '{}'", s))?;
+        let numerator =
+            numerator.trim().parse().map_err(|_| format!("invalid numerator '{}'", s))?;
+        let denominator =
+            denominator.trim().parse().map_err(|_| format!("invalid denominator '{}'", s))?;
+        Self::new(numerator, denominator)
+    }
+
+    pub fn turns(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The angle doubled `times` times, modulo one turn. Each iteration of `z² + c` doubles the
+    /// external angle of the point.
+    pub fn doubled(&self, times: usize) -> Self {
+        let modulus = self.denominator as u128;
+        let (mut factor, mut base, mut exponent) = (1u128, 2 % modulus, times);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                factor = factor * base % modulus;
+            }
+            base = base * base % modulus;
+            exponent >>= 1;
+        }
+
+        Self {
+            numerator: (self.numerator as u128 * factor % modulus) as u64,
+            denominator: self.denominator,
+        }
+    }
+}
+
+/// The distance between two angles, in turns, going whichever way round is shorter
+pub fn angle_distance(a: f64, b: f64) -> f64 {
+    let distance = (a - b).rem_euclid(1.0);
+    distance.min(1.0 - distance)
+}
+
+/// Trace the external ray of the Mandelbrot set at `angle` inwards from far outside the set.
+///
+/// Each step moves to the parameter whose orbit lands on the next of `sharpness` equipotentials
+/// per dwell band, solved with Newton's method, until `depth` dwell bands have been crossed.
+/// The returned points start far from the set and end close to where the ray lands.
+pub fn trace_ray(angle: ExternalAngle, depth: usize, sharpness: usize) -> Vec<Complex<f64>> {
+    let mut c = Complex::from_polar(RAY_ESCAPE_RADIUS, TAU * angle.turns());
+    let mut points = vec![c];
+    let mut angle = angle;
+
+    for band in 0..depth {
+        for step in 0..sharpness {
+            let level = 0.5f64.powf((step as f64 + 0.5) / sharpness as f64);
+            let radius = RAY_ESCAPE_RADIUS.powf(level);
+            let target = Complex::from_polar(radius, TAU * angle.turns());
+
+            for _ in 0..RAY_NEWTON_STEPS {
+                let (mut z, mut dc) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+                for _ in 0..=band {
+                    dc = 2.0 * z * dc + 1.0;
+                    z = z * z + c;
+                }
+                let next = c - (z - target) / dc;
+                if !next.re.is_finite() || !next.im.is_finite() {
+                    return points;
+                }
+                let moved = (next - c).norm_sqr();
+                c = next;
+                if moved <= 1e-30 * c.norm_sqr() {
+                    break;
+                }
+            }
+            points.push(c);
+        }
+        angle = angle.doubled(1);
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_external_angle() {
+        assert_eq!(ExternalAngle::parse("1/3"), ExternalAngle::new(1, 3));
+        assert_eq!(ExternalAngle::parse("4/3").unwrap().turns(), 1.0 / 3.0);
+        assert!(ExternalAngle::parse("1/0").is_err());
+        assert!(ExternalAngle::parse("1").is_err());
+        assert!(ExternalAngle::parse("a/3").is_err());
+    }
+
+    #[test]
+    fn test_doubled() {
+        let third = ExternalAngle::new(1, 3).unwrap();
+        assert_eq!(third.doubled(1), ExternalAngle::new(2, 3).unwrap());
+        assert_eq!(third.doubled(2), third);
+
+        let seventh = ExternalAngle::new(1, 7).unwrap();
+        assert_eq!(seventh.doubled(2), ExternalAngle::new(4, 7).unwrap());
+        assert_eq!(seventh.doubled(3 * 1000), seventh);
+    }
+
+    #[test]
+    fn test_angle_distance() {
+        assert_eq!(angle_distance(0.25, 0.5), 0.25);
+        assert!((angle_distance(0.95, 0.05) - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_trace_ray_lands_on_tip() {
+        // The ray at half a turn runs along the negative real axis to -2
+        let points = trace_ray(ExternalAngle::new(1, 2).unwrap(), 16, 4);
+        assert_eq!(points.len(), 1 + 16 * 4);
+        assert!(points.iter().all(|c| c.im.abs() < 1e-6 && c.re < -2.0));
+        assert!((points.last().unwrap().re + 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_trace_ray_lands_on_misiurewicz_point() {
+        // The ray at 1/6 lands on c = i, whose orbit falls into a 2-cycle after one step
+        let points = trace_ray(ExternalAngle::new(1, 6).unwrap(), 30, 8);
+        assert!((points.last().unwrap() - Complex::new(0.0, 1.0)).norm() < 1e-3);
+        assert!(points.iter().all(|c| c.im > 0.0));
+    }
+
+    #[test]
+    fn test_trace_ray_approaches_root() {
+        // The ray at 1/3 heads for the root of the period 2 bulb at -0.75, slowly as it is a
+        // parabolic point
+        let points = trace_ray(ExternalAngle::new(1, 3).unwrap(), 40, 8);
+        let root = Complex::new(-0.75, 0.0);
+        assert!((points.last().unwrap() - root).norm() < 0.1);
+        assert!(points.windows(2).all(|pair| (pair[1] - root).norm() < (pair[0] - root).norm()));
+    }
+}
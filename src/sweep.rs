@@ -0,0 +1,243 @@
+use crate::algorithms::{
+    get_plotting_algorithm, Escape, Julia, Multibrot, Parameters, PlottingAlgorithm,
+};
+use crate::coloring::Coloring;
+use crate::font::{draw_text, text_size};
+use crate::mandelbrot::{Canvas, ComplexRegion};
+use crate::montage::Montage;
+use crate::types::Dimensions;
+use num::complex::Complex;
+use rayon::prelude::*;
+
+/// Font pixels of dark margin left around each label
+const LABEL_MARGIN: usize = 2;
+
+/// The parameter varied across the tiles of a contact sheet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sweep {
+    /// Iteration limit, from the first value to the second
+    Limit(usize, usize),
+    /// Exponent `d` of `z^d + c`, from the first value to the second
+    Exponent(f64, f64),
+    /// Escape radius, from the first value to the second
+    Radius(f64, f64),
+    /// Julia sets for `c` spaced evenly along a path of straight segments
+    Julia(Vec<Complex<f64>>),
+}
+
+/// The parameters of one tile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub limit: usize,
+    pub exponent: f64,
+    pub escape: Escape,
+    pub parameters: Parameters,
+    pub julia: Option<Complex<f64>>,
+}
+
+impl Variant {
+    /// The text annotating this variant's tile, naming the swept parameter
+    pub fn label(&self, sweep: &Sweep) -> String {
+        match (sweep, self.julia) {
+            (Sweep::Limit(..), _) => format!("limit={}", self.limit),
+            (Sweep::Exponent(..), _) => format!("d={}", format_number(self.exponent)),
+            (Sweep::Radius(..), _) => format!("r={}", format_number(self.escape.radius)),
+            (Sweep::Julia(_), Some(c)) => {
+                let sign = if c.im < 0.0 { '-' } else { '+' };
+                format!("c={}{}{}i", format_number(c.re), sign, format_number(c.im.abs()))
+            }
+            (Sweep::Julia(_), None) => String::new(),
+        }
+    }
+
+    /// The algorithm rendering this variant, built on the named base algorithm
+    fn algorithm(
+        &self, name: &str,
+    ) -> Result<Box<dyn PlottingAlgorithm + Send + Sync>, String> {
+        let algorithm = match self.exponent == 2.0 {
+            true => get_plotting_algorithm(name, self.escape, &self.parameters)?,
+            false => Box::new(Multibrot { exponent: self.exponent, escape: self.escape }),
+        };
+        Ok(match self.julia {
+            Some(c) => Box::new(Julia { algorithm, c }),
+            None => algorithm,
+        })
+    }
+}
+
+/// Round to three decimal places and drop trailing zeros
+fn format_number(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Linear interpolation from `from` to `to` at step `step` of `steps`
+fn interpolate(from: f64, to: f64, step: usize, steps: usize) -> f64 {
+    if steps < 2 {
+        from
+    } else {
+        from + (to - from) * step as f64 / (steps - 1) as f64
+    }
+}
+
+impl Sweep {
+    /// `steps` variants of `base`, spread evenly over the sweep
+    pub fn variants(&self, base: &Variant, steps: usize) -> Vec<Variant> {
+        (0..steps)
+            .map(|step| match self {
+                Sweep::Limit(from, to) => Variant {
+                    limit: interpolate(*from as f64, *to as f64, step, steps).round() as usize,
+                    ..base.clone()
+                },
+                Sweep::Exponent(from, to) => {
+                    Variant { exponent: interpolate(*from, *to, step, steps), ..base.clone() }
+                }
+                Sweep::Radius(from, to) => {
+                    let radius = interpolate(*from, *to, step, steps);
+                    Variant { escape: Escape { radius, ..base.escape }, ..base.clone() }
+                }
+                Sweep::Julia(path) => {
+                    let c = point_along(path, interpolate(0.0, 1.0, step, steps));
+                    Variant { julia: Some(c), ..base.clone() }
+                }
+            })
+            .collect()
+    }
+}
+
+/// The point `fraction` of the way along `path` by length
+fn point_along(path: &[Complex<f64>], fraction: f64) -> Complex<f64> {
+    let length: f64 = path.windows(2).map(|segment| (segment[1] - segment[0]).norm()).sum();
+    let mut remaining = length * fraction;
+
+    for segment in path.windows(2) {
+        let segment_length = (segment[1] - segment[0]).norm();
+        if remaining <= segment_length && segment_length > 0.0 {
+            return segment[0] + (segment[1] - segment[0]) * (remaining / segment_length);
+        }
+        remaining -= segment_length;
+    }
+    *path.last().expect("a sweep path needs at least one point")
+}
+
+/// Render every variant over `region` into its own labeled tile of a montage `columns` wide
+pub fn render_contact_sheet(
+    sweep: &Sweep, variants: &[Variant], algorithm: &str, coloring: &Coloring,
+    region: ComplexRegion, tile: Dimensions, columns: usize,
+) -> Result<Montage, String> {
+    let columns = columns.clamp(1, variants.len().max(1));
+    let grid = Dimensions::new(columns, variants.len().div_ceil(columns));
+    let scale = (tile.width / 160).max(1);
+
+    let tiles: Vec<Vec<u8>> = variants
+        .par_iter()
+        .map(|variant| {
+            let canvas = Canvas::new(variant.algorithm(algorithm)?, coloring.clone());
+            let mut pixels = vec![0; tile.total_pixels()];
+            canvas.render(
+                &mut pixels,
+                (tile.width, tile.height),
+                region.upper_left,
+                region.lower_right,
+                variant.limit,
+                false,
+            );
+
+            let label = variant.label(sweep);
+            let (width, height) = text_size(&label, scale);
+            let margin = LABEL_MARGIN * scale;
+            for row in pixels.chunks_mut(tile.width).take(height + 2 * margin) {
+                let end = (width + 2 * margin).min(tile.width);
+                row[..end].fill(0);
+            }
+            draw_text(&mut pixels, tile.width, margin, margin, &label, scale, u8::MAX);
+            Ok(pixels)
+        })
+        .collect::<Result<_, String>>()?;
+
+    let mut montage = Montage::new(grid, tile);
+    for (index, pixels) in tiles.iter().enumerate() {
+        montage.place(index % columns, index / columns, pixels);
+    }
+    Ok(montage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::Bailout;
+    use crate::mandelbrot::calculate_region;
+
+    fn base() -> Variant {
+        Variant {
+            limit: 100,
+            exponent: 2.0,
+            escape: Escape::default(),
+            parameters: Parameters::default(),
+            julia: None,
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let limits: Vec<usize> =
+            Sweep::Limit(10, 20).variants(&base(), 3).iter().map(|v| v.limit).collect();
+        assert_eq!(limits, vec![10, 15, 20]);
+
+        let exponents = Sweep::Exponent(2.0, 3.0).variants(&base(), 5);
+        assert_eq!(exponents[1].exponent, 2.25);
+        assert_eq!(exponents[1].limit, 100);
+        assert_eq!(exponents[1].label(&Sweep::Exponent(2.0, 3.0)), "d=2.25");
+
+        let radii = Sweep::Radius(2.0, 10.0).variants(&base(), 3);
+        assert_eq!(radii[1].escape, Escape::new(6.0, Bailout::Circle));
+        assert_eq!(radii[1].label(&Sweep::Radius(2.0, 10.0)), "r=6");
+    }
+
+    #[test]
+    fn test_julia_path() {
+        let path = vec![Complex::new(0.0, 0.0), Complex::new(1.0, 0.0), Complex::new(1.0, 3.0)];
+        let sweep = Sweep::Julia(path);
+        let variants = sweep.variants(&base(), 5);
+        let points: Vec<Complex<f64>> = variants.iter().map(|v| v.julia.unwrap()).collect();
+
+        assert_eq!(points[0], Complex::new(0.0, 0.0));
+        assert_eq!(points[1], Complex::new(1.0, 0.0));
+        assert_eq!(points[2], Complex::new(1.0, 1.0));
+        assert_eq!(points[4], Complex::new(1.0, 3.0));
+        assert_eq!(variants[3].label(&sweep), "c=1+2i");
+
+        let variant = Variant { julia: Some(Complex::new(-0.8, -0.1564)), ..base() };
+        assert_eq!(variant.label(&sweep), "c=-0.8-0.156i");
+    }
+
+    #[test]
+    fn test_contact_sheet_layout() {
+        let sweep = Sweep::Limit(5, 50);
+        let variants = sweep.variants(&base(), 3);
+        let region = calculate_region(4.0, (0.0, 0.0));
+        let tile = Dimensions::new(64, 32);
+        let montage = render_contact_sheet(
+            &sweep,
+            &variants,
+            "escape_time",
+            &Coloring::Iterations,
+            region,
+            tile,
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(montage.grid, Dimensions::new(2, 2));
+        // The missing fourth tile is left black
+        let bottom = &montage.pixels[32 * 128..];
+        assert!(bottom.chunks(128).all(|row| row[64..].iter().all(|&p| p == 0)));
+        // Labels are drawn in white on a dark background
+        assert!(montage.pixels[..32 * 128].contains(&u8::MAX));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::algorithms::OrbitVisitor;
+use crate::coloring::{Accumulator, Texture};
+use num::complex::Complex;
+
+/// A shape in the complex plane that orbits are measured against.
+///
+/// Trap coloring shades each pixel by how close its orbit comes to the trap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrbitTrap {
+    Point(Complex<f64>),
+    /// A line through `point`, at `angle` radians from the real axis
+    Line { point: Complex<f64>, angle: f64 },
+    /// A horizontal and a vertical line crossing at the given point
+    Cross(Complex<f64>),
+    Circle { center: Complex<f64>, radius: f64 },
+    /// A texture covering the square of width `size` around `center`
+    Image { texture: Texture, center: Complex<f64>, size: f64 },
+}
+
+impl OrbitTrap {
+    /// Parse a trap description of the form `<shape>:<parameters>`:
+    ///
+    /// * `point:x,y`
+    /// * `line:x,y,degrees`
+    /// * `cross:x,y`
+    /// * `circle:x,y,radius`
+    /// * `image:path,x,y,size`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (shape, parameters) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <shape>:<parameters>, found '{}'", s))?;
+
+        if shape == "image" {
+            let (path, rest) = parameters
+                .split_once(',')
+                .ok_or("expected image:<path>,<x>,<y>,<size>")?;
+            let [x, y, size] = parse_numbers(rest)?;
+            let texture = Texture::open(path)?;
+            return Ok(OrbitTrap::Image { texture, center: Complex::new(x, y), size });
+        }
+
+        match shape {
+            "point" => {
+                let [x, y] = parse_numbers(parameters)?;
+                Ok(OrbitTrap::Point(Complex::new(x, y)))
+            }
+            "line" => {
+                let [x, y, degrees] = parse_numbers(parameters)?;
+                Ok(OrbitTrap::Line { point: Complex::new(x, y), angle: degrees.to_radians() })
+            }
+            "cross" => {
+                let [x, y] = parse_numbers(parameters)?;
+                Ok(OrbitTrap::Cross(Complex::new(x, y)))
+            }
+            "circle" => {
+                let [x, y, radius] = parse_numbers(parameters)?;
+                Ok(OrbitTrap::Circle { center: Complex::new(x, y), radius })
+            }
+            _ => Err(format!("unknown trap shape '{}'", shape)),
+        }
+    }
+
+    /// The distance from `z` to the trap
+    pub fn distance(&self, z: Complex<f64>) -> f64 {
+        match self {
+            OrbitTrap::Point(point) => (z - point).norm(),
+            OrbitTrap::Line { point, angle } => {
+                // Project onto the line's normal
+                let offset = z - point;
+                (offset.im * angle.cos() - offset.re * angle.sin()).abs()
+            }
+            OrbitTrap::Cross(point) => (z.re - point.re).abs().min((z.im - point.im).abs()),
+            OrbitTrap::Circle { center, radius } => ((z - center).norm() - radius).abs(),
+            OrbitTrap::Image { center, .. } => (z - center).norm(),
+        }
+    }
+}
+
+fn parse_numbers<const N: usize>(s: &str) -> Result<[f64; N], String> {
+    let values: Vec<&str> = s.split(',').collect();
+    if values.len() != N {
+        return Err(format!("expected {} comma separated numbers, found '{}'", N, s));
+    }
+
+    let mut numbers = [0.0; N];
+    for (number, value) in numbers.iter_mut().zip(values) {
+        *number = value.trim().parse().map_err(|_| format!("invalid number '{}'", value))?;
+    }
+    Ok(numbers)
+}
+
+/// Tracks the closest approach of an orbit to a trap
+pub struct TrapDistance<'a> {
+    trap: &'a OrbitTrap,
+    falloff: f64,
+    nearest: f64,
+    closest: Complex<f64>,
+}
+
+impl<'a> TrapDistance<'a> {
+    /// Track distances to `trap`, whose brightness decays over `falloff`
+    pub fn new(trap: &'a OrbitTrap, falloff: f64) -> Self {
+        Self { trap, falloff, nearest: f64::INFINITY, closest: Complex::new(0.0, 0.0) }
+    }
+}
+
+impl OrbitVisitor for TrapDistance<'_> {
+    fn visit(&mut self, z: Complex<f64>) {
+        let distance = self.trap.distance(z);
+        if distance < self.nearest {
+            self.nearest = distance;
+            self.closest = z;
+        }
+    }
+}
+
+impl Accumulator for TrapDistance<'_> {
+    /// Shade the orbit between 0 (never came near) and 1 (touched the trap), whether or not it
+    /// escaped.
+    ///
+    /// Image traps sample their texture where the orbit came closest to the image center, and
+    /// are dark when that point lies outside of the image.
+    fn shade(&self, _escape: Option<usize>, _escape_radius: f64) -> Option<f64> {
+        match self.trap {
+            OrbitTrap::Image { texture, center, size } => {
+                let u = (self.closest.re - center.re) / size + 0.5;
+                let v = (center.im - self.closest.im) / size + 0.5;
+                if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) {
+                    Some(texture.sample(u, v) as f64 / u8::MAX as f64)
+                } else {
+                    Some(0.0)
+                }
+            }
+            _ => Some((-self.nearest / self.falloff).exp()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traps() {
+        assert_eq!(OrbitTrap::parse("point:0.5,-1"), Ok(OrbitTrap::Point(Complex::new(0.5, -1.0))));
+        assert_eq!(OrbitTrap::parse("cross:0,0"), Ok(OrbitTrap::Cross(Complex::new(0.0, 0.0))));
+        assert_eq!(
+            OrbitTrap::parse("circle:0,1,0.25"),
+            Ok(OrbitTrap::Circle { center: Complex::new(0.0, 1.0), radius: 0.25 })
+        );
+        assert_eq!(
+            OrbitTrap::parse("line:0,0,90"),
+            Ok(OrbitTrap::Line { point: Complex::new(0.0, 0.0), angle: 90f64.to_radians() })
+        );
+
+        assert!(OrbitTrap::parse("circle:0,1").is_err());
+        assert!(OrbitTrap::parse("point").is_err());
+        assert!(OrbitTrap::parse("star:0,0").is_err());
+        assert!(OrbitTrap::parse("image:missing.png,0,0,1").is_err());
+    }
+
+    #[test]
+    fn test_trap_distances() {
+        let z = Complex::new(3.0, 4.0);
+        assert_eq!(OrbitTrap::Point(Complex::new(0.0, 0.0)).distance(z), 5.0);
+        assert_eq!(OrbitTrap::Cross(Complex::new(0.0, 0.0)).distance(z), 3.0);
+        assert_eq!(
+            OrbitTrap::Circle { center: Complex::new(0.0, 0.0), radius: 2.0 }.distance(z),
+            3.0
+        );
+
+        let horizontal = OrbitTrap::Line { point: Complex::new(0.0, 1.0), angle: 0.0 };
+        assert_eq!(horizontal.distance(z), 3.0);
+        let vertical = OrbitTrap::parse("line:1,0,90").unwrap();
+        assert!((vertical.distance(z) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_trap_distance_tracks_closest_approach() {
+        let trap = OrbitTrap::Point(Complex::new(0.0, 0.0));
+        let mut tracker = TrapDistance::new(&trap, 0.5);
+        for z in [Complex::new(2.0, 0.0), Complex::new(0.0, 0.5), Complex::new(-1.0, 1.0)] {
+            tracker.visit(z);
+        }
+        assert_eq!(tracker.nearest, 0.5);
+        assert_eq!(tracker.closest, Complex::new(0.0, 0.5));
+        assert_eq!(tracker.shade(None, 2.0), Some((-1.0f64).exp()));
+    }
+
+    #[test]
+    fn test_image_trap_samples_closest_texel() {
+        let texture = Texture::new(2, 2, vec![0, 64, 128, 255]);
+        let trap = OrbitTrap::Image { texture, center: Complex::new(0.0, 0.0), size: 2.0 };
+
+        let mut tracker = TrapDistance::new(&trap, 1.0);
+        tracker.visit(Complex::new(5.0, 5.0));
+        tracker.visit(Complex::new(0.5, -0.5));
+        assert_eq!(tracker.shade(None, 2.0), Some(1.0));
+
+        let mut tracker = TrapDistance::new(&trap, 1.0);
+        tracker.visit(Complex::new(5.0, 5.0));
+        assert_eq!(tracker.shade(Some(1), 2.0), Some(0.0));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::algorithms::PlottingAlgorithm;
+use crate::mandelbrot::{calculate_region, Canvas, ComplexRegion, ExponentialMap};
+use crate::types::Dimensions;
+use num::complex::Complex;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+/// A zoom into `center`, from a region `start` wide to one `end` wide, as a series of frames
+/// shrinking by the same factor each time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zoom {
+    pub center: Complex<f64>,
+    pub start: f64,
+    pub end: f64,
+    pub frames: usize,
+}
+
+impl Zoom {
+    /// The width of the region shown by `frame`
+    pub fn width(&self, frame: usize) -> f64 {
+        if self.frames < 2 {
+            return self.start;
+        }
+        self.start * (self.end / self.start).powf(frame as f64 / (self.frames - 1) as f64)
+    }
+
+    pub fn region(&self, frame: usize) -> ComplexRegion {
+        calculate_region(self.width(frame), (self.center.re, self.center.im))
+    }
+
+    /// The strip every frame of `bounds` pixels can be resampled from. It reaches out to the
+    /// corners of the first frame and in to half a pixel of the last, and goes around in enough
+    /// columns that the edges of the frames are sampled at least as finely as their pixels.
+    pub fn strip(&self, bounds: Dimensions) -> (ExponentialMap, usize) {
+        let pixels = bounds.width.max(bounds.height);
+        let map = ExponentialMap::new(
+            self.center,
+            self.start.max(self.end) / 2.0 * 2f64.sqrt(),
+            (PI * pixels as f64).ceil() as usize,
+        );
+        let rows = map.rows_to(self.start.min(self.end) / pixels as f64 / 2.0);
+        (map, rows)
+    }
+}
+
+/// Render the strip of `rows` rows for `map`, a row per rayon task
+pub fn render_strip(
+    canvas: &Canvas<impl PlottingAlgorithm + Sync>, map: &ExponentialMap, rows: usize,
+    limit: usize, invert: bool,
+) -> Vec<u8> {
+    let mut strip = vec![0; map.width * rows];
+    let bounds = Dimensions::new(map.width, rows);
+    strip.par_chunks_mut(map.width).enumerate().for_each(|(row, line)| {
+        canvas.render_projected(line, bounds, row, map, limit, invert);
+    });
+    strip
+}
+
+/// Resample a frame showing `region` from the exponential map `strip`, interpolating between
+/// the four strip pixels around each frame pixel. Frame pixels nearer the center than the last
+/// row of the strip take that row's values.
+pub fn reconstruct(
+    strip: &[u8], map: &ExponentialMap, frame: &mut [u8], bounds: Dimensions,
+    region: ComplexRegion,
+) {
+    assert_eq!(frame.len(), bounds.total_pixels());
+    assert_eq!(strip.len() % map.width, 0);
+    let rows = strip.len() / map.width;
+    let at = |x: usize, y: usize| strip[y * map.width + x % map.width] as f64;
+
+    frame.par_chunks_mut(bounds.width).enumerate().for_each(|(row, line)| {
+        for (column, pixel) in line.iter_mut().enumerate() {
+            let point = region.pixel_to_point(bounds, (column, row).into());
+            let (x, y) = map.point_to_position(point);
+            let y = y.clamp(0.0, (rows - 1) as f64);
+
+            let (left, top) = (x.floor() as usize, y.floor() as usize);
+            let bottom = (top + 1).min(rows - 1);
+            let (fx, fy) = (x.fract(), y.fract());
+            // The strip wraps around from its last column to its first
+            let upper = at(left, top) * (1.0 - fx) + at(left + 1, top) * fx;
+            let lower = at(left, bottom) * (1.0 - fx) + at(left + 1, bottom) * fx;
+            *pixel = (upper * (1.0 - fy) + lower * fy).round() as u8;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::EscapeTime;
+    use crate::coloring::Coloring;
+
+    #[test]
+    fn test_zoom_widths() {
+        let zoom = Zoom { center: Complex::new(-0.75, 0.1), start: 4.0, end: 4e-6, frames: 7 };
+        assert_eq!(zoom.width(0), 4.0);
+        assert!((zoom.width(3) / 4e-3 - 1.0).abs() < 1e-12);
+        assert!((zoom.width(6) / 4e-6 - 1.0).abs() < 1e-12);
+
+        // Six octaves of zoom at 100 pixels take about 6 ln 2 / (2π / 315) ≈ 210 rows, and a
+        // few more for the corners and the last half pixel
+        let zoom = Zoom { end: 4.0 / 64.0, ..zoom };
+        let (map, rows) = zoom.strip(Dimensions::new(100, 80));
+        assert_eq!(map.width, 315);
+        assert!(rows > 210 && rows < 600);
+    }
+
+    #[test]
+    fn test_reconstruct_radial() {
+        // A strip that darkens row by row resamples to a frame that darkens towards the center
+        let map = ExponentialMap::new(Complex::new(0.0, 0.0), 2.0, 64);
+        let strip: Vec<u8> = (0..64 * 100).map(|i| u8::MAX - (i / 64) as u8).collect();
+        let bounds = Dimensions::new(20, 20);
+        let mut frame = vec![0; bounds.total_pixels()];
+        reconstruct(&strip, &map, &mut frame, bounds, calculate_region(2.0, (0.0, 0.0)));
+
+        let middle = bounds.width * 10;
+        assert!(frame[middle..middle + 10].windows(2).all(|pair| pair[0] > pair[1]));
+        // The center is past the last row
+        assert_eq!(frame[middle + 10], u8::MAX - 99);
+    }
+
+    #[test]
+    fn test_reconstruct_matches_render() {
+        let canvas = Canvas::new(Box::<EscapeTime>::default(), Coloring::Iterations);
+        let zoom = Zoom { center: Complex::new(-0.75, 0.1), start: 0.5, end: 0.05, frames: 3 };
+        let bounds = Dimensions::new(64, 64);
+        let (map, rows) = zoom.strip(bounds);
+        let strip = render_strip(&canvas, &map, rows, 64, false);
+
+        for frame in 0..zoom.frames {
+            let region = zoom.region(frame);
+            let mut resampled = vec![0; bounds.total_pixels()];
+            reconstruct(&strip, &map, &mut resampled, bounds, region);
+            let mut direct = vec![0; bounds.total_pixels()];
+            canvas.render(
+                &mut direct,
+                (bounds.width, bounds.height),
+                region.upper_left,
+                region.lower_right,
+                64,
+                false,
+            );
+
+            // Resampling blurs the edges of the bands, but most pixels come out the same
+            let close = resampled.iter().zip(&direct).filter(|(a, b)| a.abs_diff(**b) <= 8).count();
+            assert!(close > bounds.total_pixels() * 9 / 10, "frame {}: {} close", frame, close);
+        }
+    }
+}